@@ -278,7 +278,7 @@ export class App extends ReactiveComponent {
 					icon='ald'
 					tx={{
 						sender: runtime.sudo.key,
-						call: calls.bondedToken.init(1, 1),
+						call: calls.bondedToken.init(1, 1, 0, 1000000000, null),
 					}}
 				/>
 				<div style={{paddingBottom: '1em'}}>
@@ -288,7 +288,7 @@ export class App extends ReactiveComponent {
 						content="Buy Tokens"
 						tx={{
 							sender: runtime.sudo.key,
-							call: calls.bondedToken.buy(this.buyAmount)
+							call: calls.bondedToken.buy(this.buyAmount, null)
 						}}
 					/>
 				</div>
@@ -299,7 +299,7 @@ export class App extends ReactiveComponent {
 						content="Sell Tokens"
 						tx={{
 							sender: runtime.sudo.key,
-							call: calls.bondedToken.sell(this.sellAmount)
+							call: calls.bondedToken.sell(this.sellAmount, null)
 						}}
 					/>
 				</div>
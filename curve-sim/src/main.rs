@@ -0,0 +1,108 @@
+//! Replays a script of trades against a bonded curve and prints the price, supply
+//! and reserve after each one, for checking curve parameters before genesis.
+//!
+//! ```text
+//! curve-sim <polynomial|sqrt|sigmoid> <exponent> <slope> <steepness> <base-price> <script>
+//! ```
+//!
+//! Each line of the script is `buy <tokens>` or `sell <tokens>`. Blank lines and
+//! lines starting with `#` are skipped.
+
+use std::{env, fs, process};
+
+use bonded_curve::{CurveParams, CurveShape};
+
+/// A single step of a trade script.
+enum Trade {
+	Buy(u128),
+	Sell(u128),
+}
+
+fn main() {
+	let args: Vec<String> = env::args().collect();
+	if args.len() != 7 {
+		eprintln!("Usage: {} <polynomial|sqrt|sigmoid> <exponent> <slope> <steepness> <base-price> <script>", args[0]);
+		process::exit(2);
+	}
+
+	let params = match parse_params(&args[1..6]) {
+		Ok(params) => params,
+		Err(e) => fail(&e),
+	};
+	let script = match fs::read_to_string(&args[6]) {
+		Ok(script) => script,
+		Err(e) => fail(&format!("Cannot read {}: {}", args[6], e)),
+	};
+	let trades = match parse_script(&script) {
+		Ok(trades) => trades,
+		Err(e) => fail(&e),
+	};
+
+	let (mut supply, mut reserve) = (0u128, 0u128);
+	println!("{:>5} {:>5} {:>20} {:>24} {:>24} {:>24} {:>24}", "step", "side", "tokens", "value", "supply", "reserve", "spot price");
+	for (step, trade) in trades.iter().enumerate() {
+		let (side, tokens, value) = match *trade {
+			Trade::Buy(tokens) => {
+				let to = supply.checked_add(tokens).unwrap_or_else(|| fail("Supply overflows."));
+				let cost = params.area(supply, to, true).unwrap_or_else(|| fail("Cost overflows."));
+				supply = to;
+				reserve = reserve.checked_add(cost).unwrap_or_else(|| fail("Reserve overflows."));
+				("buy", tokens, cost)
+			}
+			Trade::Sell(tokens) => {
+				let to = supply.checked_sub(tokens).unwrap_or_else(|| fail("Sold more tokens than exist."));
+				let ret = params.area(to, supply, false).unwrap_or_else(|| fail("Return overflows."));
+				supply = to;
+				reserve = reserve.checked_sub(ret).unwrap_or_else(|| fail("Reserve cannot cover the sell."));
+				("sell", tokens, ret)
+			}
+		};
+		println!("{:>5} {:>5} {:>20} {:>24} {:>24} {:>24} {:>24}", step + 1, side, tokens, value, supply, reserve, params.spot_price(supply));
+	}
+}
+
+/// Parses the shape, exponent, slope, steepness and base price arguments.
+fn parse_params(args: &[String]) -> Result<CurveParams, String> {
+	let shape = match args[0].as_str() {
+		"polynomial" => CurveShape::Polynomial,
+		"sqrt" => CurveShape::Sqrt,
+		"sigmoid" => CurveShape::Sigmoid,
+		other => return Err(format!("Unknown curve shape {}.", other)),
+	};
+	let number = |arg: &String| arg.parse::<u128>().map_err(|_| format!("{} is not a number.", arg));
+	Ok(CurveParams {
+		shape,
+		exponent: number(&args[1])?,
+		slope: number(&args[2])?,
+		steepness: number(&args[3])?,
+		base_price: number(&args[4])?,
+	})
+}
+
+/// Parses a trade script, one trade per line.
+fn parse_script(script: &str) -> Result<Vec<Trade>, String> {
+	let mut trades = Vec::new();
+	for (n, line) in script.lines().enumerate() {
+		let line = line.trim();
+		if line.is_empty() || line.starts_with('#') {
+			continue;
+		}
+
+		let mut words = line.split_whitespace();
+		let side = words.next().unwrap_or_default();
+		let tokens = words.next()
+			.and_then(|tokens| tokens.parse::<u128>().ok())
+			.ok_or_else(|| format!("Line {}: expected a token amount.", n + 1))?;
+		trades.push(match side {
+			"buy" => Trade::Buy(tokens),
+			"sell" => Trade::Sell(tokens),
+			other => return Err(format!("Line {}: unknown trade {}.", n + 1, other)),
+		});
+	}
+	Ok(trades)
+}
+
+fn fail(message: &str) -> ! {
+	eprintln!("{}", message);
+	process::exit(1);
+}
@@ -0,0 +1,26 @@
+//! The area under a valid curve never panics, brackets the exact value and grows
+//! with the interval.
+
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+mod input;
+
+fuzz_target!(|data: &[u8]| {
+	let mut input = input::Input::new(data);
+	let (params, max_supply) = match input.curve() {
+		Some(curve) => curve,
+		None => return,
+	};
+	let to = input.up_to(max_supply);
+	let from = input.up_to(to);
+
+	let upper = params.area(from, to, true).expect("area overflowed below the maximum supply");
+	let lower = params.area(from, to, false).expect("area overflowed below the maximum supply");
+	assert!(lower <= upper, "rounding down gave more than rounding up");
+
+	if to < max_supply {
+		let wider = params.area(from, to + 1, false).expect("area overflowed below the maximum supply");
+		assert!(wider >= lower, "area shrank as the interval grew");
+	}
+});
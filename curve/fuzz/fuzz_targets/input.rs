@@ -0,0 +1,54 @@
+//! Decodes fuzzer bytes into curve parameters and amounts.
+
+use bonded_curve::{CurveParams, CurveShape, MAX_EXPONENT};
+
+/// Reads fixed-size values off the front of the fuzzer input, padding with zeroes
+/// once it runs out.
+pub struct Input<'a>(&'a [u8]);
+
+impl<'a> Input<'a> {
+	pub fn new(data: &'a [u8]) -> Self {
+		Input(data)
+	}
+
+	pub fn byte(&mut self) -> u8 {
+		let (first, rest) = self.0.split_first().map_or((0, &[][..]), |(b, rest)| (*b, rest));
+		self.0 = rest;
+		first
+	}
+
+	pub fn u128(&mut self) -> u128 {
+		let mut bytes = [0u8; 16];
+		for b in bytes.iter_mut() {
+			*b = self.byte();
+		}
+		u128::from_le_bytes(bytes)
+	}
+
+	/// A value no larger than `max`, biased towards small values so curves that
+	/// validate are common.
+	pub fn up_to(&mut self, max: u128) -> u128 {
+		let value = self.u128() >> (self.byte() % 128);
+		if max == u128::max_value() { value } else { value % (max + 1) }
+	}
+
+	/// Curve parameters that validate up to the returned maximum supply, if the
+	/// input describes any.
+	pub fn curve(&mut self) -> Option<(CurveParams, u128)> {
+		let shape = match self.byte() % 3 {
+			0 => CurveShape::Polynomial,
+			1 => CurveShape::Sqrt,
+			_ => CurveShape::Sigmoid,
+		};
+		let params = CurveParams {
+			shape,
+			exponent: self.byte() as u128 % (MAX_EXPONENT + 1),
+			slope: self.up_to(u128::max_value()),
+			steepness: self.up_to(u128::max_value()),
+			base_price: self.up_to(u128::max_value()),
+		};
+		let max_supply = self.up_to(u128::max_value());
+		params.validate(max_supply).ok()?;
+		Some((params, max_supply))
+	}
+}
@@ -0,0 +1,29 @@
+//! The token search returns exactly the largest affordable amount.
+
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use bonded_curve::{bps, max_tokens};
+
+mod input;
+
+fuzz_target!(|data: &[u8]| {
+	let mut input = input::Input::new(data);
+	let (params, max_supply) = match input.curve() {
+		Some(curve) => curve,
+		None => return,
+	};
+	let supply = input.up_to(max_supply);
+	let spend = input.u128();
+	let entry_fee = (input.byte() as u32) * 40;
+
+	let cost = |tokens: u128| supply.checked_add(tokens)
+		.filter(|to| *to <= max_supply)
+		.and_then(|to| params.area(supply, to, true))
+		.and_then(|cost| cost.checked_add(bps(cost, entry_fee)));
+	let fits = |tokens: u128| cost(tokens).map_or(false, |total| total <= spend);
+
+	let tokens = max_tokens(fits);
+	assert!(tokens == 0 || fits(tokens), "returned an amount that does not fit");
+	assert!(!tokens.checked_add(1).map_or(false, fits), "a larger amount also fits");
+});
@@ -0,0 +1,26 @@
+//! Buying tokens and selling them back, in one go or in two parts, never returns
+//! more than was paid.
+
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+mod input;
+
+fuzz_target!(|data: &[u8]| {
+	let mut input = input::Input::new(data);
+	let (params, max_supply) = match input.curve() {
+		Some(curve) => curve,
+		None => return,
+	};
+	let to = input.up_to(max_supply);
+	let from = input.up_to(to);
+	let split = from + input.up_to(to - from);
+
+	let cost = params.area(from, to, true).expect("area overflowed below the maximum supply");
+	let whole = params.area(from, to, false).expect("area overflowed below the maximum supply");
+	assert!(whole <= cost, "selling back returned more than buying cost");
+
+	let first = params.area(split, to, false).expect("area overflowed below the maximum supply");
+	let second = params.area(from, split, false).expect("area overflowed below the maximum supply");
+	assert!(first + second <= cost, "selling back in parts returned more than buying cost");
+});
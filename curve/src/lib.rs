@@ -0,0 +1,347 @@
+//! Pure integer math of the bonded token price curves, shared by the runtime and
+//! off-chain tooling so both quote identically.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use parity_codec_derive::{Encode, Decode};
+
+#[cfg(feature = "wasm")]
+mod wasm;
+
+/// Largest polynomial exponent, bounding the work of pricing a trade.
+pub const MAX_EXPONENT: u128 = 10;
+/// Denominator of the basis-point rates used for fees and rewards.
+pub const BPS: u128 = 10_000;
+
+/// The family of functions the price curve belongs to.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum CurveShape {
+	/// `price = slope * supply^exponent`
+	Polynomial,
+	/// `price = slope * sqrt(supply)`
+	Sqrt,
+	/// `price = slope * supply / sqrt(supply^2 + steepness)`, rising towards `slope`.
+	Sigmoid,
+}
+
+impl Default for CurveShape {
+	fn default() -> Self {
+		CurveShape::Polynomial
+	}
+}
+
+/// The parameters that fully describe a price curve.
+#[derive(Encode, Decode, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct CurveParams {
+	/// Family of the curve.
+	pub shape: CurveShape,
+	/// Exponent of a polynomial curve.
+	pub exponent: u128,
+	/// Scale of the curve; the price ceiling of a sigmoid.
+	pub slope: u128,
+	/// How slowly a sigmoid approaches its ceiling.
+	pub steepness: u128,
+	/// Constant added to the price of every shape, the price at zero supply.
+	pub base_price: u128,
+}
+
+impl CurveParams {
+	/// Checks the parameters describe a usable curve up to `max_supply`.
+	pub fn validate(&self, max_supply: u128) -> Result<(), &'static str> {
+		if self.slope == 0 {
+			return Err("Slope must be positive.");
+		}
+		if max_supply == 0 {
+			return Err("Maximum supply must be positive.");
+		}
+		match self.shape {
+			CurveShape::Polynomial if self.exponent > MAX_EXPONENT => return Err("Exponent is too large."),
+			CurveShape::Sigmoid if self.steepness == 0 => return Err("Sigmoid steepness must be positive."),
+			_ => (),
+		}
+		if self.integral_bound(max_supply, true).is_none() {
+			return Err("Curve overflows before the maximum supply.");
+		}
+		if self.checked_spot_price(max_supply).is_none() {
+			return Err("Price overflows before the maximum supply.");
+		}
+		Ok(())
+	}
+
+	/// Area under the curve between supplies `from` and `to`, rounded up when
+	/// `round_up` is set and down otherwise.
+	pub fn area(&self, from: u128, to: u128, round_up: bool) -> Option<u128> {
+		if self.shape == CurveShape::Polynomial {
+			// Exact until the final division.
+			let area = self.scaled_integral(to)?.checked_sub(self.scaled_integral(from)?)?;
+			let nexp = self.exponent.checked_add(1)?;
+			let base = self.base_price.checked_mul(to.checked_sub(from)?)?;
+			return div_round(area, nexp, round_up).checked_add(base);
+		}
+
+		// The other shapes involve square roots that integers can only bracket, so the
+		// area is taken between the outer bounds when rounding up and the inner ones
+		// when rounding down.
+		let area = if round_up {
+			self.integral_bound(to, true)?.saturating_sub(self.integral_bound(from, false)?)
+		} else {
+			self.integral_bound(to, false)?.saturating_sub(self.integral_bound(from, true)?)
+		};
+		Some(area)
+	}
+
+	/// The curve with its base price moved so the area from zero to `supply` comes as
+	/// close to `reserve` as it can without exceeding it. `None` if even a zero base
+	/// price leaves the area above `reserve`.
+	pub fn rebased(&self, supply: u128, reserve: u128) -> Option<CurveParams> {
+		if supply == 0 {
+			return Some(self.clone());
+		}
+		let unbased = CurveParams { base_price: 0, ..self.clone() };
+		let area = unbased.area(0, supply, true)?;
+		let base_price = reserve.checked_sub(area)? / supply;
+		Some(CurveParams { base_price, ..unbased })
+	}
+
+	/// Marginal price of the curve at `supply`, the derivative of the integral.
+	/// Saturates at the largest representable price.
+	pub fn spot_price(&self, supply: u128) -> u128 {
+		self.checked_spot_price(supply).unwrap_or_else(u128::max_value)
+	}
+
+	fn checked_spot_price(&self, supply: u128) -> Option<u128> {
+		let price = match self.shape {
+			CurveShape::Polynomial => checked_pow(supply, self.exponent)?.checked_mul(self.slope)?,
+			CurveShape::Sqrt => self.slope.checked_mul(isqrt(supply))?,
+			CurveShape::Sigmoid => {
+				let root = isqrt(supply.checked_mul(supply)?.checked_add(self.steepness)?);
+				if root == 0 {
+					0
+				} else {
+					self.slope.checked_mul(supply)? / root
+				}
+			}
+		};
+		price.checked_add(self.base_price)
+	}
+
+	/// Upper or lower bound of the integral of the curve from zero to `x`, up to a
+	/// constant that cancels out in `area`.
+	fn integral_bound(&self, x: u128, upper: bool) -> Option<u128> {
+		let sqrt = |n: u128| if upper { ceil_sqrt(n) } else { isqrt(n) };
+		let integral = match self.shape {
+			CurveShape::Polynomial => {
+				let nexp = self.exponent.checked_add(1)?;
+				div_round(self.scaled_integral(x)?, nexp, upper)
+			}
+			// 2/3 * slope * x^(3/2)
+			CurveShape::Sqrt => {
+				let scaled = self.slope.checked_mul(2)?.checked_mul(x)?.checked_mul(sqrt(x))?;
+				div_round(scaled, 3, upper)
+			}
+			// slope * sqrt(x^2 + steepness)
+			CurveShape::Sigmoid => {
+				let inner = x.checked_mul(x)?.checked_add(self.steepness)?;
+				self.slope.checked_mul(sqrt(inner))?
+			}
+		};
+		integral.checked_add(self.base_price.checked_mul(x)?)
+	}
+
+	/// Integral of a polynomial curve from zero to `to_x`, multiplied by
+	/// `exponent + 1` so it stays exact in integers: `slope * to_x^(exponent + 1)`.
+	fn scaled_integral(&self, to_x: u128) -> Option<u128> {
+		let nexp = self.exponent.checked_add(1)?;
+		checked_pow(to_x, nexp)?.checked_mul(self.slope)
+	}
+}
+
+/// `n / d` rounded up or down.
+fn div_round(n: u128, d: u128, round_up: bool) -> u128 {
+	n / d + if round_up && n % d != 0 { 1 } else { 0 }
+}
+
+/// Checked `base^exp`, returning `None` on overflow.
+fn checked_pow(base: u128, exp: u128) -> Option<u128> {
+	if base <= 1 || exp == 0 {
+		return Some(if exp == 0 { 1 } else { base });
+	}
+
+	let mut result: u128 = 1;
+	for _ in 0..exp {
+		result = result.checked_mul(base)?;
+	}
+	Some(result)
+}
+
+/// Largest integer whose square does not exceed `n`.
+pub fn isqrt(n: u128) -> u128 {
+	if n < 2 {
+		return n;
+	}
+
+	// Newton's method from above converges without overshooting.
+	let mut x = n;
+	let mut y = n / 2 + n % 2;
+	while y < x {
+		x = y;
+		y = (x + n / x) / 2;
+	}
+	x
+}
+
+/// Smallest integer whose square is at least `n`.
+fn ceil_sqrt(n: u128) -> u128 {
+	let root = isqrt(n);
+	if root * root == n { root } else { root + 1 }
+}
+
+/// `value * rate / BPS` without overflowing for any `value`.
+pub fn bps(value: u128, rate: u32) -> u128 {
+	let rate = rate as u128;
+	value / BPS * rate + value % BPS * rate / BPS
+}
+
+/// Largest token amount for which `fits` holds, given that it holds for every
+/// smaller amount whenever it holds for a larger one.
+pub fn max_tokens<F: Fn(u128) -> bool>(fits: F) -> u128 {
+	let mut lo: u128 = 0;
+	let mut hi: u128 = 1;
+	while fits(hi) {
+		if hi == u128::max_value() {
+			return hi;
+		}
+		lo = hi;
+		hi = hi.checked_mul(2).unwrap_or_else(u128::max_value);
+	}
+
+	while hi - lo > 1 {
+		let mid = lo + (hi - lo) / 2;
+		if fits(mid) {
+			lo = mid;
+		} else {
+			hi = mid;
+		}
+	}
+	lo
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn linear(slope: u128, base_price: u128) -> CurveParams {
+		CurveParams { shape: CurveShape::Polynomial, exponent: 1, slope, steepness: 0, base_price }
+	}
+
+	fn curves() -> [CurveParams; 6] {
+		[
+			linear(1, 0),
+			linear(3, 7),
+			CurveParams { shape: CurveShape::Polynomial, exponent: 0, slope: 5, steepness: 0, base_price: 0 },
+			CurveParams { shape: CurveShape::Polynomial, exponent: 3, slope: 1, steepness: 0, base_price: 2 },
+			CurveParams { shape: CurveShape::Sqrt, exponent: 0, slope: 3, steepness: 0, base_price: 0 },
+			CurveParams { shape: CurveShape::Sigmoid, exponent: 0, slope: 1_000, steepness: 10_000, base_price: 1 },
+		]
+	}
+
+	#[test]
+	fn polynomial_area_is_exact_up_to_rounding() {
+		// slope * x^2 / 2 from 0 to 3 is 4.5.
+		assert_eq!(linear(1, 0).area(0, 3, true), Some(5));
+		assert_eq!(linear(1, 0).area(0, 3, false), Some(4));
+		// Whole areas round the same both ways, and the base price adds per token.
+		assert_eq!(linear(2, 10).area(2, 4, true), Some(32));
+		assert_eq!(linear(2, 10).area(2, 4, false), Some(32));
+		assert_eq!(linear(1, 0).area(4, 2, true), None);
+	}
+
+	#[test]
+	fn area_rounded_up_covers_area_rounded_down() {
+		for curve in curves().iter() {
+			for &(from, to) in &[(0, 1), (0, 17), (5, 6), (100, 1_000), (12_345, 67_890)] {
+				let up = curve.area(from, to, true).unwrap();
+				let down = curve.area(from, to, false).unwrap();
+				assert!(up >= down, "{:?} from {} to {}", curve, from, to);
+			}
+		}
+	}
+
+	#[test]
+	fn buying_is_never_free() {
+		for curve in curves().iter() {
+			for &supply in &[0, 1, 2, 1_000] {
+				assert!(curve.area(supply, supply + 1, true).unwrap() > 0, "{:?} at {}", curve, supply);
+			}
+		}
+	}
+
+	#[test]
+	fn buy_then_sell_is_never_profitable() {
+		for curve in curves().iter() {
+			for &supply in &[0, 1, 9, 250, 40_000] {
+				for &tokens in &[1, 2, 3, 10, 999] {
+					let cost = curve.area(supply, supply + tokens, true).unwrap();
+					// Selling in one go and token by token both return at most the cost.
+					let back = curve.area(supply, supply + tokens, false).unwrap();
+					let piecewise: u128 = (0..tokens)
+						.map(|i| curve.area(supply + i, supply + i + 1, false).unwrap())
+						.sum();
+					assert!(back <= cost, "{:?} at {} for {}", curve, supply, tokens);
+					assert!(piecewise <= cost, "{:?} at {} for {}", curve, supply, tokens);
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn max_tokens_finds_the_largest_fitting_amount() {
+		assert_eq!(max_tokens(|n| n <= 0), 0);
+		assert_eq!(max_tokens(|n| n <= 1), 1);
+		assert_eq!(max_tokens(|n| n <= 1_000), 1_000);
+		assert_eq!(max_tokens(|_| true), u128::max_value());
+
+		let curve = linear(2, 5);
+		let budget = 1_000_000;
+		let tokens = max_tokens(|n| curve.area(100, 100 + n, true).map_or(false, |cost| cost <= budget));
+		assert!(curve.area(100, 100 + tokens, true).unwrap() <= budget);
+		assert!(curve.area(100, 100 + tokens + 1, true).unwrap() > budget);
+	}
+
+	#[test]
+	fn bps_matches_the_exact_product() {
+		assert_eq!(bps(12_345, 250), 12_345 * 250 / BPS);
+		assert_eq!(bps(u128::max_value(), BPS as u32), u128::max_value());
+		assert_eq!(bps(9_999, 1), 0);
+	}
+
+	#[test]
+	fn rebasing_lets_a_steeper_curve_take_over_the_reserve() {
+		let supply = 1_000;
+		let current = linear(2, 500);
+		let reserve = current.area(0, supply, true).unwrap();
+
+		let steeper = linear(3, 500);
+		assert!(steeper.area(0, supply, true).unwrap() > reserve);
+
+		let rebased = steeper.rebased(supply, reserve).unwrap();
+		assert_eq!(rebased.slope, 3);
+		assert!(rebased.base_price < current.base_price);
+		assert!(rebased.area(0, supply, true).unwrap() <= reserve);
+		assert!(reserve - rebased.area(0, supply, true).unwrap() < supply);
+	}
+
+	#[test]
+	fn rebasing_fails_when_no_base_price_covers_the_curve() {
+		let supply = 1_000;
+		let reserve = linear(2, 0).area(0, supply, true).unwrap();
+		assert_eq!(linear(3, 0).rebased(supply, reserve), None);
+	}
+
+	#[test]
+	fn rebasing_an_empty_supply_keeps_the_curve() {
+		assert_eq!(linear(3, 7).rebased(0, 0), Some(linear(3, 7)));
+	}
+}
@@ -0,0 +1,71 @@
+//! JavaScript bindings so front-ends quote with exactly the runtime's math.
+//!
+//! JavaScript numbers cannot hold a `u128`, so amounts cross the boundary as
+//! decimal strings. Quotes that overflow come back as `undefined`.
+
+use wasm_bindgen::prelude::*;
+
+use crate::{bps, max_tokens, CurveParams, CurveShape};
+
+/// A curve to quote trades against.
+#[wasm_bindgen]
+pub struct Curve {
+	params: CurveParams,
+}
+
+#[wasm_bindgen]
+impl Curve {
+	/// Builds a curve from its shape name (`polynomial`, `sqrt` or `sigmoid`) and
+	/// parameters.
+	#[wasm_bindgen(constructor)]
+	pub fn new(shape: &str, exponent: &str, slope: &str, steepness: &str, base_price: &str) -> Result<Curve, JsValue> {
+		let shape = match shape {
+			"polynomial" => CurveShape::Polynomial,
+			"sqrt" => CurveShape::Sqrt,
+			"sigmoid" => CurveShape::Sigmoid,
+			_ => return Err(JsValue::from_str("Unknown curve shape.")),
+		};
+		Ok(Curve {
+			params: CurveParams {
+				shape,
+				exponent: parse(exponent)?,
+				slope: parse(slope)?,
+				steepness: parse(steepness)?,
+				base_price: parse(base_price)?,
+			},
+		})
+	}
+
+	/// Curve cost of buying `tokens` at `supply`, rounded up as the runtime charges it.
+	#[wasm_bindgen(js_name = costToBuy)]
+	pub fn cost_to_buy(&self, supply: &str, tokens: &str) -> Result<Option<String>, JsValue> {
+		let supply = parse(supply)?;
+		let to = match supply.checked_add(parse(tokens)?) {
+			Some(to) => to,
+			None => return Ok(None),
+		};
+		Ok(self.params.area(supply, to, true).map(|cost| cost.to_string()))
+	}
+
+	/// Marginal price at `supply`.
+	#[wasm_bindgen(js_name = spotPrice)]
+	pub fn spot_price(&self, supply: &str) -> Result<String, JsValue> {
+		Ok(self.params.spot_price(parse(supply)?).to_string())
+	}
+
+	/// Most tokens `spend` buys at `supply` once the entry tribute of `entry_fee`
+	/// basis points is added to the curve cost.
+	#[wasm_bindgen(js_name = tokensForSpend)]
+	pub fn tokens_for_spend(&self, supply: &str, spend: &str, entry_fee: u32) -> Result<String, JsValue> {
+		let (supply, spend) = (parse(supply)?, parse(spend)?);
+		let fits = |tokens: u128| supply.checked_add(tokens)
+			.and_then(|to| self.params.area(supply, to, true))
+			.and_then(|cost| cost.checked_add(bps(cost, entry_fee)))
+			.map_or(false, |total| total <= spend);
+		Ok(max_tokens(fits).to_string())
+	}
+}
+
+fn parse(value: &str) -> Result<u128, JsValue> {
+	value.parse().map_err(|_| JsValue::from_str("Expected a non-negative integer."))
+}
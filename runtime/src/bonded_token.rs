@@ -1,19 +1,888 @@
 use rstd::prelude::*;
+use rstd::{cmp, borrow::Borrow};
 use parity_codec::Codec;
-use support::{decl_module, decl_storage, decl_event, ensure, StorageValue, StorageMap, Parameter, dispatch::Result};
-use {balances, system::{self, ensure_signed}};
-use runtime_primitives::traits::{CheckedSub, CheckedAdd, Member, SimpleArithmetic, As};
-// use runtime_io;
+use parity_codec_derive::{Encode, Decode};
+use support::{decl_module, decl_storage, decl_event, ensure, StorageValue, StorageMap, Parameter, dispatch::{Dispatchable, Result}};
+use {balances::{self, EnsureAccountLiquid}, system::{self, ensure_signed, ensure_root}, timestamp};
+use runtime_primitives::traits::{CheckedSub, CheckedAdd, CheckedMul, Member, SimpleArithmetic, As, Zero, Bounded, Hash, StaticLookup};
+pub use bonded_curve::{CurveShape, CurveParams, isqrt};
+use bonded_curve::BPS;
+use runtime_io;
 
 /// The module's configuration trait.
-pub trait Trait: system::Trait + balances::Trait {
+pub trait Trait: system::Trait + balances::Trait + timestamp::Trait {
 	/// A wrapper over `u128` type to denominate the balance of this token.
-	type TokenBalance: Parameter + Member + SimpleArithmetic + Codec + Default + Copy + As<usize> + As<u64>;
+	type TokenBalance: Parameter + Member + SimpleArithmetic + Codec + Default + Copy;
 
 	/// The overarching event type.
 	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// Handler invoked with the attached data when tokens arrive through `transfer_and_call`.
+	type OnTokenReceived: OnTokenReceived<Self::AccountId>;
+
+	/// Handler invoked after every buy.
+	type OnBuy: OnTrade<Self::AccountId>;
+
+	/// Handler invoked after every sell.
+	type OnSell: OnTrade<Self::AccountId>;
+
+	/// Yield source idle reserve can be deployed into.
+	type ReserveManager: ReserveManager<Self::Balance>;
+
+	/// Bonded token that this one can be converted into.
+	type Counterpart: BondedCurve<Self::AccountId>;
+
+	/// External price feed for features that compare the curve to the market.
+	type PriceOracle: ProvidesPrice<Self::BlockNumber>;
+
+	/// Receiver of the spot price and time-weighted average price every block.
+	type PriceFeed: PriceFeed<Self::BlockNumber>;
+
+	/// Callee handed flash minted tokens by `flash_mint`.
+	type FlashBorrower: FlashBorrower<Self::AccountId>;
+
+	/// Receiver of tribute left over once the insurance, creator and beneficiary shares
+	/// are taken. This module credits it to the funding pool; `()` burns it.
+	type FeeHandler: OnUnbalanced<Self::Balance>;
+
+	/// Checked conversion between token amounts and the reserve currency's balance type.
+	type BalanceConversion: TryConvert<u128, Self::Balance> + TryConvert<Self::Balance, u128>;
+
+	/// Fewest tokens a single trade or order may move.
+	const MIN_TRADE: u128;
+	/// Highest entry tribute governance may set, in basis points.
+	const MAX_ENTRY_FEE: u32;
+	/// Highest exit tribute governance may set, in basis points.
+	const MAX_EXIT_FEE: u32;
+	/// Highest maximum supply a curve may be initialized with.
+	const MAX_SUPPLY: u128;
+	/// Most spenders a single owner may have allowances for.
+	const MAX_ALLOWANCES: u32;
+	/// Whether each account's total spend and proceeds are recorded.
+	const TRACK_COST_BASIS: bool;
+}
+
+/// Conversion from `A` to `B` that fails instead of truncating.
+pub trait TryConvert<A, B> {
+	/// Converts `a`, or returns `None` if it does not fit in `B`.
+	fn try_convert(a: A) -> Option<B>;
+}
+
+/// Conversion for runtimes whose balance type is the token amount type itself.
+pub struct IdentityConversion;
+
+impl<A> TryConvert<A, A> for IdentityConversion {
+	fn try_convert(a: A) -> Option<A> {
+		Some(a)
+	}
+}
+
+/// Somewhere reserve currency can be put to work, e.g. staking.
+pub trait ReserveManager<Balance> {
+	/// Takes `amount` of reserve currency to deploy.
+	fn deploy(amount: Balance) -> Result;
+	/// Returns up to `amount` of deployed currency, reporting how much came back.
+	fn withdraw(amount: Balance) -> Balance;
+}
+
+impl<Balance: Zero> ReserveManager<Balance> for () {
+	fn deploy(_amount: Balance) -> Result {
+		Err("No reserve manager is configured.")
+	}
+	fn withdraw(_amount: Balance) -> Balance {
+		Zero::zero()
+	}
+}
+
+/// Something that takes currency the module has collected and no longer accounts for.
+pub trait OnUnbalanced<Balance> {
+	/// Takes `amount`, which has already left its payer.
+	fn on_unbalanced(amount: Balance);
+}
+
+impl<Balance> OnUnbalanced<Balance> for () {
+	fn on_unbalanced(_amount: Balance) {}
+}
+
+/// Something that reacts to trades of this token.
+pub trait OnTrade<AccountId> {
+	/// Called after `who` traded `tokens` for `value` currency.
+	fn on_trade(who: &AccountId, tokens: u128, value: u128);
+}
+
+impl<AccountId> OnTrade<AccountId> for () {
+	fn on_trade(_who: &AccountId, _tokens: u128, _value: u128) {}
+}
+
+/// Something that acts on tokens sent to it through `transfer_and_call`.
+pub trait OnTokenReceived<AccountId> {
+	/// Called after `value` tokens moved from `from` to `to`. Returning an error
+	/// reverses the transfer.
+	fn on_token_received(from: &AccountId, to: &AccountId, value: u128, data: &[u8]) -> Result;
+}
+
+impl<AccountId> OnTokenReceived<AccountId> for () {
+	fn on_token_received(_from: &AccountId, _to: &AccountId, _value: u128, _data: &[u8]) -> Result {
+		Ok(())
+	}
+}
+
+/// Something that puts flash minted tokens to work within the extrinsic that minted
+/// them, e.g. an arbitrage or liquidation strategy.
+pub trait FlashBorrower<AccountId> {
+	/// Called once `amount` tokens are minted to `who`, with `fee` already taken. By the
+	/// time it returns, `who` must hold `amount` unlocked tokens to burn.
+	fn on_flash_mint(who: &AccountId, amount: u128, fee: u128, data: &[u8]) -> Result;
+}
+
+impl<AccountId> FlashBorrower<AccountId> for () {
+	fn on_flash_mint(_who: &AccountId, _amount: u128, _fee: u128, _data: &[u8]) -> Result {
+		Err("No flash borrower is configured.")
+	}
+}
+
+/// An external source of the token's price.
+pub trait ProvidesPrice<BlockNumber> {
+	/// Latest price of one token in reserve currency and the block it was reported
+	/// at, if any.
+	fn price() -> Option<(u128, BlockNumber)>;
+}
+
+impl<BlockNumber> ProvidesPrice<BlockNumber> for () {
+	fn price() -> Option<(u128, BlockNumber)> {
+		None
+	}
+}
+
+/// Something that receives this token's price, e.g. an oracle module serving it to
+/// the rest of the chain without reading this module's storage.
+pub trait PriceFeed<BlockNumber> {
+	/// Called at the end of every block with the spot price and the latest
+	/// time-weighted average price of one token in reserve currency.
+	fn on_price(spot_price: u128, twap: u128, now: BlockNumber);
+}
+
+impl<BlockNumber> PriceFeed<BlockNumber> for () {
+	fn on_price(_spot_price: u128, _twap: u128, _now: BlockNumber) {}
+}
+
+/// A bonded token that currency can be converted into.
+pub trait BondedCurve<AccountId> {
+	/// Tokens that `value` currency buys, net of the entry tribute.
+	fn quote_buy(value: u128) -> u128;
+	/// Buys as many tokens as `value` currency pays for into the account of `who`,
+	/// failing if that is fewer than `min_tokens`.
+	fn buy_with(who: &AccountId, value: u128, min_tokens: u128) -> Result;
+	/// Checks `buy_with` would succeed once `who` holds `value` currency, without
+	/// changing anything.
+	fn ensure_can_buy(who: &AccountId, value: u128, min_tokens: u128) -> Result;
+}
+
+impl<AccountId> BondedCurve<AccountId> for () {
+	fn quote_buy(_value: u128) -> u128 {
+		0
+	}
+
+	fn buy_with(_who: &AccountId, _value: u128, _min_tokens: u128) -> Result {
+		Err("No counterpart token is configured.")
+	}
+
+	fn ensure_can_buy(_who: &AccountId, _value: u128, _min_tokens: u128) -> Result {
+		Err("No counterpart token is configured.")
+	}
+}
+
+/// Something that can penalize token holders. Only modules configured with it can
+/// slash, so it should be given to trusted modules such as staking or disputes.
+pub trait SlashTokens<AccountId> {
+	/// Burns up to `amount` tokens of `who`, returning how many were burned. With
+	/// `insure`, the reserve backing the burned tokens moves to the insurance fund
+	/// instead of staying behind for the remaining holders.
+	fn slash(who: &AccountId, amount: u128, insure: bool) -> u128;
+}
+
+/// Reserve, unreserve and slash over token balances, the interface a staking-style
+/// module needs to secure roles with this token.
+pub trait StakeTokens<AccountId> {
+	/// Tokens of `who` held as stake.
+	fn staked(who: &AccountId) -> u128;
+	/// Moves `amount` of the free tokens of `who` into stake, where they cannot move.
+	fn stake(who: &AccountId, amount: u128) -> Result;
+	/// Frees up to `amount` of the stake of `who`, returning how much was freed.
+	fn unstake(who: &AccountId, amount: u128) -> u128;
+	/// Burns up to `amount` of the stake of `who`, returning how much was burned.
+	fn slash_stake(who: &AccountId, amount: u128) -> u128;
+}
+
+/// Lock, valuation and liquidation over token balances, the interface a lending
+/// module needs to accept this token as collateral.
+pub trait CollateralAsset<AccountId> {
+	/// Tokens of `who` locked as collateral.
+	fn collateral(who: &AccountId) -> u128;
+	/// Locks `amount` of the free tokens of `who` as collateral, where they cannot move.
+	fn lock_collateral(who: &AccountId, amount: u128) -> Result;
+	/// Frees up to `amount` of the collateral of `who`, returning how much was freed.
+	fn unlock_collateral(who: &AccountId, amount: u128) -> u128;
+	/// Currency selling `amount` tokens on the curve would pay, net of the exit tribute.
+	fn collateral_value(amount: u128) -> u128;
+	/// Sells `amount` of the collateral of `who` on the curve, paying the proceeds to
+	/// `to` and returning them.
+	fn liquidate(who: &AccountId, amount: u128, to: &AccountId) -> rstd::result::Result<u128, &'static str>;
+}
+
+/// Breakdown of a trade against the curve at the current supply.
+#[derive(Encode, Decode, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct TradeQuote {
+	/// Currency the buyer pays in total, or the seller's curve return before fees.
+	pub gross: u128,
+	/// Entry or exit tribute taken out of the trade.
+	pub tribute: u128,
+	/// Currency added to the reserve by a buy, or paid out to the seller.
+	pub net: u128,
+	/// Spot price once the trade is made.
+	pub spot_price: u128,
+}
+
+/// State trie keys a value may be stored under: the current layout, and the layout
+/// from before keys were hashed, which entries move off as they are written.
+#[derive(Encode, Decode, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct StorageKeys {
+	/// Key under the current layout.
+	pub current: Vec<u8>,
+	/// Key under the legacy layout.
+	pub legacy: Vec<u8>,
+}
+
+/// Everything a wallet shows about the token, read in one call.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct TokenState {
+	/// Total token supply.
+	pub supply: u128,
+	/// Currency backing the supply, liquid and deployed.
+	pub reserve: u128,
+	/// Marginal price at the current supply.
+	pub spot_price: u128,
+	/// Parameters of the curve, with the slope in effect now.
+	pub curve: CurveParams,
+	/// Base entry tribute in basis points.
+	pub entry_fee: u32,
+	/// Base exit tribute in basis points.
+	pub exit_fee: u32,
+	/// Whether tributes are charged.
+	pub fees_enabled: bool,
+	/// Whether the curve is closed to trades, before init, during the pre-sale or after
+	/// a failed solvency check.
+	pub paused: bool,
+}
+
+/// Named curves that can be chosen at init instead of hand-computed parameters.
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum CurvePreset {
+	/// `price = slope * supply`
+	Linear,
+	/// `price = slope * supply^2`
+	Quadratic,
+	/// `price = slope * sqrt(supply)`
+	Sqrt,
+	/// `price = slope * supply / sqrt(supply^2 + steepness)`
+	Sigmoid { steepness: u128 },
+	/// Constant reserve ratio in parts per million. Only ratios of the form
+	/// `1 / (n + 1)` have a polynomial curve, e.g. 500_000 is linear.
+	Bancor { ratio: u32 },
+}
+
+impl CurvePreset {
+	/// Validated curve parameters for this preset scaled by `slope`.
+	pub fn params(&self, slope: u128) -> rstd::result::Result<CurveParams, &'static str> {
+		let polynomial = |exponent| CurveParams { shape: CurveShape::Polynomial, exponent, slope, steepness: 0, base_price: 0 };
+		Ok(match *self {
+			CurvePreset::Linear => polynomial(1),
+			CurvePreset::Quadratic => polynomial(2),
+			CurvePreset::Sqrt => CurveParams { shape: CurveShape::Sqrt, exponent: 0, slope, steepness: 0, base_price: 0 },
+			CurvePreset::Sigmoid { steepness } => CurveParams { shape: CurveShape::Sigmoid, exponent: 0, slope, steepness, base_price: 0 },
+			CurvePreset::Bancor { ratio } => {
+				let ratio = ratio as u128;
+				ensure!(ratio > 0 && ratio <= PPM, "Reserve ratio must be within (0, 1].");
+				ensure!(PPM % ratio == 0, "Reserve ratio has no integer exponent.");
+				polynomial(PPM / ratio - 1)
+			}
+		})
+	}
+}
+
+/// Most tokens an account may transfer or sell per window of blocks, set by the
+/// account itself or imposed by the admin.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct SpendLimit<BlockNumber> {
+	/// Tokens that can leave the account per window.
+	pub amount: u128,
+	/// Blocks in a window.
+	pub window: BlockNumber,
+	/// Block the current window began at.
+	pub window_start: BlockNumber,
+	/// Tokens transferred or sold in the current window.
+	pub spent: u128,
+	/// Whether the admin imposed the limit, so only the admin can remove it.
+	pub imposed: bool,
+	/// Block a self-imposed limit being lifted stops applying at.
+	pub lifts_at: Option<BlockNumber>,
+}
+
+/// Tokens that unlock linearly from `start`, at `per_block` tokens a block. Nothing
+/// unlocks before `cliff`, when everything accrued since the start unlocks at once.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct VestingSchedule<BlockNumber> {
+	/// Tokens locked at the start.
+	pub locked: u128,
+	/// Tokens unlocked each block after the start.
+	pub per_block: u128,
+	/// Block unlocking begins at.
+	pub start: BlockNumber,
+	/// Block before which nothing unlocks.
+	pub cliff: BlockNumber,
+}
+
+impl<BlockNumber: SimpleArithmetic + Copy + As<u64>> VestingSchedule<BlockNumber> {
+	/// Tokens still locked at block `now`.
+	pub fn locked_at(&self, now: BlockNumber) -> u128 {
+		if now <= self.start || now < self.cliff {
+			return self.locked;
+		}
+		let elapsed: u64 = (now - self.start).as_();
+		self.locked.saturating_sub(self.per_block.saturating_mul(elapsed as u128))
+	}
+}
+
+/// Everything kept about an account apart from its balance, in one entry so a transfer
+/// reads it once and an account without any state has no entry at all.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct AccountData<AccountId, BlockNumber> {
+	/// Vesting schedules restricting how much of the balance can move.
+	pub vesting: Vec<VestingSchedule<BlockNumber>>,
+	/// Bought lots that cannot be sold yet, as (unlock block, tokens).
+	pub buy_locks: Vec<(BlockNumber, u128)>,
+	/// Block of the latest buy.
+	pub last_buy: Option<BlockNumber>,
+	/// Tokens bought in total.
+	pub purchased: u128,
+	/// Currency spent buying, if cost basis is tracked.
+	pub total_spent: u128,
+	/// Currency received selling, if cost basis is tracked.
+	pub total_proceeds: u128,
+	/// Tokens locked behind vetoes, which cannot move until withdrawn.
+	pub veto_locked: u128,
+	/// Tokens held as stake by another module.
+	pub staked: u128,
+	/// Tokens locked as collateral by a lending module.
+	pub collateral: u128,
+	/// Delegate the voting power is delegated to, with the tokens delegated.
+	pub delegation: Option<(AccountId, u128)>,
+	/// Second signer transfers above the threshold wait for, with the threshold.
+	pub cosigner: Option<(AccountId, u128)>,
+	/// Limit on the tokens transferred or sold per window.
+	pub spend_limit: Option<SpendLimit<BlockNumber>>,
+	/// Guardian able to reverse transfers, with the blocks transfers are held for.
+	pub reversible: Option<(AccountId, BlockNumber)>,
+	/// Average block the tokens held were acquired in, weighted by amount. `None` for
+	/// holders from before this was tracked, who do not qualify for loyalty rates.
+	pub held_since: Option<BlockNumber>,
+}
+
+/// Tokens minted to the team at init, vesting linearly over `duration` blocks after
+/// a `cliff` of blocks, both counted from init.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct TeamAllocation<AccountId, BlockNumber> {
+	/// Account the allocation is minted to.
+	pub who: AccountId,
+	/// Tokens allocated.
+	pub amount: u128,
+	/// Blocks before anything vests.
+	pub cliff: BlockNumber,
+	/// Blocks over which the whole allocation vests.
+	pub duration: BlockNumber,
+}
+
+/// Tokens escrowed from `from` and released to `to` at `rate` tokens a block.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Stream<AccountId, BlockNumber> {
+	/// Account that funded the stream.
+	pub from: AccountId,
+	/// Account the stream pays.
+	pub to: AccountId,
+	/// Tokens released each block.
+	pub rate: u128,
+	/// Block the stream started at.
+	pub start: BlockNumber,
+	/// Block the stream is fully released at.
+	pub end: BlockNumber,
+	/// Tokens the recipient has withdrawn.
+	pub withdrawn: u128,
+}
+
+impl<AccountId, BlockNumber: SimpleArithmetic + Copy + As<u64>> Stream<AccountId, BlockNumber> {
+	/// Tokens released by block `now`, withdrawn or not.
+	pub fn released_at(&self, now: BlockNumber) -> u128 {
+		let elapsed: u64 = (cmp::min(now, self.end) - cmp::min(now, self.start)).as_();
+		self.rate.saturating_mul(elapsed as u128)
+	}
+}
+
+/// Tokens held for `to` until the arbiter or the parties settle, refunded to `from`
+/// if nobody releases them by the deadline.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Escrow<AccountId, BlockNumber> {
+	/// Account that funded the escrow.
+	pub from: AccountId,
+	/// Account the tokens are released to.
+	pub to: AccountId,
+	/// Account that can release or refund the tokens.
+	pub arbiter: AccountId,
+	/// Tokens held.
+	pub amount: u128,
+	/// Block after which the funder can take the tokens back.
+	pub deadline: BlockNumber,
+}
+
+/// Tokens locked for `to` under a hash, claimable with its preimage until the timeout
+/// and refundable to `from` after it.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Htlc<AccountId, BlockNumber> {
+	/// Account that locked the tokens.
+	pub from: AccountId,
+	/// Account the tokens go to when claimed.
+	pub to: AccountId,
+	/// Tokens locked.
+	pub amount: u128,
+	/// Block from which the tokens can no longer be claimed, only refunded.
+	pub timeout: BlockNumber,
+}
+
+/// What tokens leaving an account become once its cosigner or dispute window lets
+/// them go.
+#[derive(Encode, Decode, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum Outflow<AccountId, Hash, BlockNumber> {
+	/// Paid to the recipient.
+	Transfer,
+	/// Escrowed under the arbiter until the deadline.
+	Escrow(AccountId, BlockNumber),
+	/// Streamed at the rate per block over the duration.
+	Stream(u128, BlockNumber),
+	/// Locked under the hash until the timeout.
+	Htlc(Hash, BlockNumber),
+}
+
+impl<AccountId, Hash, BlockNumber> Default for Outflow<AccountId, Hash, BlockNumber> {
+	fn default() -> Self {
+		Outflow::Transfer
+	}
+}
+
+/// A transfer above the sender's threshold, held until the cosigner approves it.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct PendingTransfer<AccountId, Hash, BlockNumber> {
+	/// Account that sent the tokens.
+	pub from: AccountId,
+	/// Account the tokens go to when approved.
+	pub to: AccountId,
+	/// Account that can approve the transfer.
+	pub cosigner: AccountId,
+	/// Tokens held.
+	pub amount: u128,
+	/// What the tokens become when approved.
+	pub outflow: Outflow<AccountId, Hash, BlockNumber>,
+}
+
+/// A transfer held for a dispute window, during which the sender or its guardian can
+/// reverse it, after which it completes on its own.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct ReversibleTransfer<AccountId, Hash, BlockNumber> {
+	/// Account that sent the tokens.
+	pub from: AccountId,
+	/// Account the tokens go to when the window ends.
+	pub to: AccountId,
+	/// Account that can reverse the transfer besides the sender.
+	pub guardian: AccountId,
+	/// Tokens held.
+	pub amount: u128,
+	/// Block the transfer completes at.
+	pub finalizes_at: BlockNumber,
+	/// What the tokens become when the window ends.
+	pub outflow: Outflow<AccountId, Hash, BlockNumber>,
+}
+
+/// A pre-sale of a fixed tranche at a price declining from `start_price` to
+/// `floor_price`. Every bidder pays the same clearing price: the price at which the
+/// tranche sold out, or the floor if it never did. Bids committed above what the
+/// tranche sells for at the clearing price are refunded pro rata.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct DutchAuction<BlockNumber> {
+	/// Tokens on sale.
+	pub tranche: u128,
+	/// Price per token at the start.
+	pub start_price: u128,
+	/// Price per token at the end.
+	pub floor_price: u128,
+	/// Block the auction opened at.
+	pub start: BlockNumber,
+	/// Block the auction closes at if the tranche does not sell out first.
+	pub end: BlockNumber,
+	/// Currency bid so far.
+	pub committed: u128,
+	/// Price the auction cleared at, once it has closed.
+	pub clearing_price: Option<u128>,
+}
+
+impl<BlockNumber: SimpleArithmetic + Copy + As<u64>> DutchAuction<BlockNumber> {
+	/// Asking price per token at block `now`.
+	pub fn price_at(&self, now: BlockNumber) -> u128 {
+		if now >= self.end {
+			return self.floor_price;
+		}
+		let elapsed: u64 = (now - self.start).as_();
+		let duration: u64 = (self.end - self.start).as_();
+		let (elapsed, duration) = (elapsed as u128, duration as u128);
+		let drop = self.start_price - self.floor_price;
+		self.start_price - (drop / duration * elapsed + drop % duration * elapsed / duration)
+	}
+
+	/// Tokens sold once the auction has cleared at `price`, at most the tranche.
+	pub fn sold_at(&self, price: u128) -> u128 {
+		cmp::min(self.committed / price, self.tranche)
+	}
+
+	/// Share of `sold` tokens and of the currency refunded that a bid of `bid` gets
+	/// once the auction has cleared at `price`.
+	pub fn claim_for(&self, bid: u128, price: u128) -> (u128, u128) {
+		let sold = self.sold_at(price);
+		let excess = self.committed - sold * price;
+		let share = |value: u128| value.checked_mul(bid)
+			.map_or_else(|| value / self.committed * bid, |product| product / self.committed);
+		(share(sold), share(excess))
+	}
+}
+
+/// A trade waiting in the per-block queue to be netted against the opposite side.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Order<AccountId> {
+	/// The account that placed the order.
+	pub who: AccountId,
+	/// Tokens still to be bought or sold. For sells these are held by the order.
+	pub tokens: u128,
+	/// Per-token price limit: the most a buyer pays, the least a seller accepts.
+	pub limit: u128,
+	/// Currency held by a buy order to pay for its fills. Unused for sells.
+	pub escrow: u128,
+	/// Whether the order has rested through a settlement, making it a maker.
+	pub resting: bool,
+}
+
+/// A dollar-cost-averaging plan buying a fixed currency amount every `interval` blocks.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct RecurringBuy<AccountId, BlockNumber> {
+	/// The account the tokens are bought for.
+	pub who: AccountId,
+	/// Currency spent on each purchase.
+	pub amount: u128,
+	/// Blocks between purchases.
+	pub interval: BlockNumber,
+	/// Purchases left to make.
+	pub remaining: u32,
+	/// Currency held for the remaining purchases.
+	pub escrow: u128,
+}
+
+/// Blocks aggregated into one volume bucket, an hour at five second blocks.
+const VOLUME_BUCKET_BLOCKS: u64 = 720;
+/// Buckets summed to report the trailing volume, a day of hourly buckets.
+const VOLUME_WINDOW_BUCKETS: u64 = 24;
+
+/// A snapshot of the curve for dashboards.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct CurveStats<BlockNumber, Balance> {
+	/// Block the snapshot was taken at.
+	pub block: BlockNumber,
+	/// Total token supply.
+	pub supply: u128,
+	/// Currency held in the reserve.
+	pub reserve: Balance,
+	/// Marginal price at the current supply.
+	pub spot_price: u128,
+	/// Currency traded over the trailing day.
+	pub volume_24h: u128,
+}
+
+/// Prices and volume of the trades in one candle window.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Candle {
+	/// Average price of the first trade in the window.
+	pub open: u128,
+	/// Highest average price traded at.
+	pub high: u128,
+	/// Lowest average price traded at.
+	pub low: u128,
+	/// Average price of the latest trade in the window.
+	pub close: u128,
+	/// Currency traded.
+	pub volume: u128,
+}
+
+/// Which side of the curve a trade was on.
+#[derive(Encode, Decode, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum TradeSide {
+	Buy,
+	Sell,
 }
 
+/// How holders' locked tokens count towards a tally.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum TallyMode {
+	/// One vote per token.
+	Linear,
+	/// Votes are the integer square root of the tokens an account locks.
+	Quadratic,
+}
+
+impl Default for TallyMode {
+	fn default() -> Self {
+		TallyMode::Linear
+	}
+}
+
+impl TallyMode {
+	/// Votes carried by `tokens` locked by one account.
+	pub fn weight(&self, tokens: u128) -> u128 {
+		match self {
+			TallyMode::Linear => tokens,
+			TallyMode::Quadratic => isqrt(tokens),
+		}
+	}
+}
+
+/// When sells are paused: selling more than `max_share` of the reserve within
+/// `window` blocks pauses sells for `cooldown` blocks.
+#[derive(Encode, Decode, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct CircuitBreaker<BlockNumber> {
+	/// Share of the reserve sells may take in one window, in basis points.
+	pub max_share: u32,
+	/// Blocks in a window.
+	pub window: BlockNumber,
+	/// Blocks sells stay paused for once the breaker trips.
+	pub cooldown: BlockNumber,
+}
+
+/// What the slope controller steers towards.
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum ControlTarget {
+	/// The oracle price: a spot price below it steepens the curve.
+	OraclePrice,
+	/// Currency traded per era: more volume than this steepens the curve.
+	Volume(u128),
+}
+
+impl Default for ControlTarget {
+	fn default() -> Self {
+		ControlTarget::OraclePrice
+	}
+}
+
+/// Gains and bounds of the controller that tunes the slope every era. Gains are in
+/// basis points, applied to the error in basis points of the target.
+#[derive(Encode, Decode, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct SlopeController {
+	/// What the controller steers towards.
+	pub target: ControlTarget,
+	/// Gain on the current error.
+	pub kp: u32,
+	/// Gain on the error summed over past eras.
+	pub ki: u32,
+	/// Gain on the change in error since the last era.
+	pub kd: u32,
+	/// Largest change to the slope in one era, in basis points of the slope.
+	pub max_step: u32,
+}
+
+/// What the slope controller carries from one era to the next.
+#[derive(Encode, Decode, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct ControllerState {
+	/// Error summed over past eras, in basis points.
+	pub integral: i64,
+	/// Error at the last era, in basis points.
+	pub last_error: i64,
+	/// Currency traded in total at the last era.
+	pub last_volume: u128,
+}
+
+/// How long a voter keeps tokens locked after the vote is decided, in exchange for
+/// multiplied votes.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum Conviction {
+	/// Locked for one vote lock era, for single votes.
+	Locked1x,
+	/// Locked for two vote lock eras, for double votes.
+	Locked2x,
+	/// Locked for four vote lock eras, for quadruple votes.
+	Locked4x,
+}
+
+impl Default for Conviction {
+	fn default() -> Self {
+		Conviction::Locked1x
+	}
+}
+
+impl Conviction {
+	/// Vote lock eras the tokens stay locked for, which is also the vote multiplier.
+	pub fn multiplier(&self) -> u128 {
+		match self {
+			Conviction::Locked1x => 1,
+			Conviction::Locked2x => 2,
+			Conviction::Locked4x => 4,
+		}
+	}
+}
+
+/// Tokens an account locked behind a veto.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Vote<BlockNumber> {
+	/// Tokens locked, delegated power included.
+	pub tokens: u128,
+	/// Part of `tokens` that is power delegated to the voter.
+	pub delegated: u128,
+	/// Conviction the vote was cast with.
+	pub conviction: Conviction,
+	/// Block the tokens can be withdrawn at once the vote is decided.
+	pub unlock: BlockNumber,
+}
+
+/// The kinds of parameter update, each of which can be tallied differently.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum UpdateCategory {
+	/// Tributes, royalties and their beneficiaries.
+	Fees,
+	/// The shape and parameters of the curve.
+	Curve,
+	/// Who governs the token and how.
+	Governance,
+	/// Everything else.
+	Other,
+}
+
+impl Default for UpdateCategory {
+	fn default() -> Self {
+		UpdateCategory::Other
+	}
+}
+
+/// A trade kept in the recent trade history.
+#[derive(Encode, Decode, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct TradeRecord<Hash, BlockNumber, Moment> {
+	/// Sequential identifier of the trade.
+	pub id: u64,
+	/// Hash of the trading account, the buyer of a matched order.
+	pub account: Hash,
+	/// Hash of the seller of a matched order, `None` for a trade with the curve.
+	pub counterparty: Option<Hash>,
+	/// Whether tokens were bought or sold.
+	pub side: TradeSide,
+	/// Tokens traded.
+	pub tokens: u128,
+	/// Average price paid or received per token.
+	pub price: u128,
+	/// Block the trade happened in.
+	pub block: BlockNumber,
+	/// Timestamp of that block.
+	pub timestamp: Moment,
+}
+
+/// A sell payout waiting for the liquid reserve to cover it.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct PendingPayout<AccountId, Balance> {
+	/// Account the payout goes to.
+	pub payee: AccountId,
+	/// Curve return taken out of the reserve, exit tribute included.
+	pub ret_amount: Balance,
+	/// Currency paid to the payee.
+	pub payout: Balance,
+}
+
+/// A claimable distribution committed to by the Merkle root of its
+/// `(account, amount)` allocations.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Distribution<Hash> {
+	/// Root of the allocation tree.
+	pub root: Hash,
+	/// Tokens that can still be claimed.
+	pub remaining: u128,
+}
+
+/// Denominator of parts-per-million ratios.
+const PPM: u128 = 1_000_000;
+/// Most vesting schedules a single account can be under.
+const MAX_VESTING_SCHEDULES: usize = 8;
+/// Most surcharge tiers in the progressive tribute schedule.
+const MAX_FEE_TIERS: usize = 8;
+/// Largest error the slope controller accumulates, in basis points, so a long
+/// deviation cannot wind it up indefinitely.
+const MAX_CONTROL_INTEGRAL: i64 = 1_000_000;
+/// Most tiers in the holding-duration exit tribute schedule.
+const MAX_LOYALTY_TIERS: usize = 8;
+/// Most pending steps a curve schedule may hold.
+const MAX_CURVE_STEPS: usize = 16;
+/// Most accounts tributes can be split between.
+const MAX_BENEFICIARIES: usize = 8;
+/// Most candle windows that can be kept.
+const MAX_CANDLE_RETENTION: u64 = 1024;
+/// Most trade history slots a single `prune` looks at.
+const MAX_PRUNE_SLOTS: u32 = 64;
+/// Longest preimage an HTLC can be claimed with, in bytes.
+const MAX_PREIMAGE_LEN: usize = 64;
+/// Most reversible transfers that can complete in a single block.
+const MAX_REVERSIBLE_DUE: usize = 64;
+/// Longest metadata URI the token can carry, in bytes.
+const MAX_METADATA_URI_LEN: usize = 128;
+const MAX_MIGRATION_BATCH: usize = 64;
+/// Most orders each of the buy and sell queues may hold.
+const MAX_ORDERS: usize = 256;
+/// Most orders of each side settled against the curve in a single settlement.
+const MAX_CURVE_SETTLEMENTS: usize = 32;
+/// Most recurring buys that can fall due in a single block.
+const MAX_RECURRING_DUE: usize = 64;
+/// Most blocks looked ahead for one with room for a recurring buy.
+const MAX_RECURRING_SLOT_SEARCH: u32 = 16;
+/// Most reserve ratio alert thresholds that can be set.
+const MAX_RATIO_ALERTS: usize = 4;
+
 /// This module's storage items.
 decl_storage! {
 	trait Store for Module<T: Trait> as bonded_token {
@@ -21,20 +890,372 @@ decl_storage! {
 		Init get(is_init): bool;
 
 		// Total Supply
-		TotalSupply get(total_supply): u128;
-		// Mapping of Accounts to Balances
-		BalanceOf get(balance_of): map T::AccountId => u128;
-		// Mapping of Accounts for `Account` to Allowance
-		Allowance get(allowance): map (T::AccountId, T::AccountId) => u128;
+		TotalSupply get(total_supply) build(|config: &GenesisConfig<T>| {
+			presale_balances(&config.presale).iter().fold(0u128, |total, (_, amount)| total.saturating_add(*amount))
+		}): u128;
+		// Balances by the hash of the account, so accounts cannot be chosen to unbalance
+		// the storage trie
+		Balances build(|config: &GenesisConfig<T>| {
+			presale_balances(&config.presale).into_iter()
+				.map(|(who, balance)| (T::Hashing::hash_of(&who), balance))
+				.collect::<Vec<_>>()
+		}): map T::Hash => u128;
+		// Balances stored by account before they were keyed by hash, moved to
+		// `Balances` the next time they change
+		BalanceOf get(legacy_balance_of): map T::AccountId => u128;
+		// Number of accounts holding a nonzero balance under the hashed key
+		HolderCount get(holder_count) build(|config: &GenesisConfig<T>| {
+			presale_balances(&config.presale).len() as u64
+		}): u64;
+		// Sum of all balances
+		BalanceTotal get(balance_total) build(|config: &GenesisConfig<T>| {
+			presale_balances(&config.presale).iter().fold(0u128, |total, (_, amount)| total.saturating_add(*amount))
+		}): u128;
+		// Sum of the balances still under the legacy key, which `BalanceTotal` leaves out
+		LegacyBalanceTotal get(legacy_balance_total): u128;
+		// Whether `LegacyBalanceTotal` has been seeded; chains started with it need no seeding
+		LegacyTotalSeeded get(legacy_total_seeded) build(|_: &GenesisConfig<T>| true): bool;
+		// Allowances by the hash of the (owner, spender) pair
+		Allowances: map T::Hash => u128;
+		// Allowances stored by account pair before they were keyed by hash, moved to
+		// `Allowances` the next time they change
+		Allowance get(legacy_allowance): map (T::AccountId, T::AccountId) => u128;
+		// Spenders an owner has allowances for, with the deposit reserved for each
+		Spenders get(spenders): map T::AccountId => Vec<(T::AccountId, T::Balance)>;
+		// Deposit reserved from an owner for each new spender
+		AllowanceDeposit get(allowance_deposit): T::Balance;
 
 		// Exponent of the polynomial
 		Exponent get(exponent): u128;
 		// Slope of the polynomial
 		Slope get(slope): u128;
+		// Family of the curve
+		Shape get(shape): CurveShape;
+		// Steepness of a sigmoid curve
+		Steepness get(steepness): u128;
+		// Price of the curve at zero supply
+		BasePrice get(base_price): u128;
+		// Supply the curve is validated up to, zero before init
+		MaxSupply get(max_supply): u128;
+		// Marginal price at the current supply
+		SpotPrice get(spot_price): u128;
 
 		// Reserve held to incentive sells
-		Reserve get(reserve): T::Balance;
+		Reserve get(reserve) build(|config: &GenesisConfig<T>| {
+			let paid = config.presale.iter()
+				.fold(0u128, |total, (_, amount, price)| total.saturating_add(amount.saturating_mul(*price)));
+			<T::BalanceConversion as TryConvert<u128, T::Balance>>::try_convert(paid)
+				.expect("Presale proceeds must fit the balance type.")
+		}): T::Balance;
+		// Reserve deployed to the reserve manager and not available for sells
+		DeployedReserve get(deployed_reserve): T::Balance;
+		// Account inflation is minted to
+		InflationBeneficiary get(inflation_beneficiary): Option<T::AccountId>;
+		// Tokens minted to the inflation beneficiary every era
+		InflationPerEra get(inflation_per_era): u128;
+		// Blocks in an era, at the end of which inflation is minted and the slope tuned,
+		// zero to disable
+		EraLength get(era_length): T::BlockNumber;
+		// Limit on sells per window that pauses them when exceeded, if enabled
+		SellBreaker get(circuit_breaker): Option<CircuitBreaker<T::BlockNumber>>;
+		// Block the current sell window began at and the currency paid out for sells in it
+		SellWindow get(sell_window): (T::BlockNumber, u128);
+		// Block sells resume at after the breaker tripped
+		SellsPausedUntil get(sells_paused_until): Option<T::BlockNumber>;
+		// Block trading was paused at after the end-of-block solvency check failed
+		Paused get(paused_at): Option<T::BlockNumber>;
+		// Controller tuning the slope every era, if enabled
+		SlopeControl get(slope_controller): Option<SlopeController>;
+		// What the slope controller carries from one era to the next
+		SlopeControlState get(slope_controller_state): ControllerState;
+		// Tokens minted by inflation, which the reserve does not back
+		InflationarySupply get(inflationary_supply): u128;
+		// Vesting of the team allocation minted at init, which the reserve does not
+		// back until vested
+		TeamVesting get(team_vesting): Option<VestingSchedule<T::BlockNumber>>;
+		// The pre-sale auction, if one was held
+		Auction get(auction): Option<DutchAuction<T::BlockNumber>>;
+		// Currency bid in the auction by each account, until claimed
+		AuctionBids get(auction_bid): map T::AccountId => u128;
+		// Tokens sold in the auction and not yet claimed
+		AuctionUnclaimed get(auction_unclaimed): u128;
+		// Slope at the start of the launch phase
+		LaunchSlope get(launch_slope): u128;
+		// Length of the launch phase in blocks
+		LaunchDuration get(launch_duration): T::BlockNumber;
+		// Block at which the launch phase ends and the configured slope applies
+		LaunchEnd get(launch_end): T::BlockNumber;
+		// Pending changes of slope and exponent, by the block they take effect at
+		CurveSchedule get(curve_schedule): Vec<(T::BlockNumber, u128, u128)>;
+		// Blocks after which an oracle price is stale, zero for no limit
+		OracleMaxAge get(oracle_max_age): T::BlockNumber;
+		// Account the curve mints into and burns from to follow the oracle price
+		RebalanceBuffer get(rebalance_buffer): Option<T::AccountId>;
+		// Divergence from the oracle price that allows a rebalance, in basis points
+		RebalanceThreshold get(rebalance_threshold): u32;
+		// Most tokens a single rebalance may mint or burn
+		MaxRebalance get(max_rebalance): u128;
+		// Sell payouts waiting for the liquid reserve, by position in the queue
+		PendingPayouts get(pending_payout): map u64 => Option<PendingPayout<T::AccountId, T::Balance>>;
+		// Position of the next payout to honor
+		PayoutHead get(payout_head): u64;
+		// Position the next queued payout is stored at
+		PayoutTail get(payout_tail): u64;
+		// Lowest ratio of the reserve to what sellers of the supply are owed that a sell
+		// may leave, in basis points, zero to disable
+		MinReserveRatio get(min_reserve_ratio): u32;
+		// Reserve ratios, in basis points, that emit an alert when a trade drops below them
+		RatioAlerts get(ratio_alerts): Vec<u32>;
+		// Reserve ratio after the last trade, in basis points
+		LastReserveRatio get(last_reserve_ratio): Option<u32>;
+		// Whether tokens can be redeemed for a pro-rata share of the reserve
+		RedemptionEnabled get(redemption_enabled): bool;
+
+		// Buy orders waiting to be settled; unfilled orders rest here until their limit is met
+		BuyQueue get(buy_queue): Vec<Order<T::AccountId>>;
+		// Sell orders waiting to be settled; unfilled orders rest here until their limit is met
+		SellQueue get(sell_queue): Vec<Order<T::AccountId>>;
+
+		// Rebate paid to resting orders matched internally, out of the taker's spread,
+		// in basis points of the matched value
+		MakerRebateRate get(maker_rebate_rate): u32;
+		// Maker rebates earned by an account and not yet claimed
+		MakerRebates get(maker_rebates): map T::AccountId => u128;
+		// Fee charged for placing an order, paid into the keeper pot
+		OrderFee get(order_fee): T::Balance;
+		// Bounty paid to whoever executes due orders with `execute_orders`
+		KeeperBounty get(keeper_bounty): T::Balance;
+		// Collected order fees available to pay keeper bounties
+		KeeperPot get(keeper_pot): T::Balance;
+
+		// Identifier for the next recurring buy
+		NextRecurringBuyId get(next_recurring_buy_id): u64;
+		// Active recurring buys by identifier
+		RecurringBuys get(recurring_buy): map u64 => Option<RecurringBuy<T::AccountId, T::BlockNumber>>;
+		// Recurring buys due at a given block
+		RecurringBuysDue get(recurring_buys_due): map T::BlockNumber => Vec<u64>;
+
+		// Currency traded per volume bucket
+		BucketVolume get(bucket_volume): map u64 => u128;
+		// Blocks between curve statistics snapshots, zero to disable
+		StatsPeriod get(stats_period): T::BlockNumber;
+		// Latest curve statistics snapshot
+		Stats get(stats): CurveStats<T::BlockNumber, T::Balance>;
+		// Spot price summed over every finalized block
+		PriceCumulative get(price_cumulative): u128;
+		// Blocks the time-weighted average price is taken over, zero to disable
+		TwapWindow get(twap_window): T::BlockNumber;
+		// Block the current averaging window began at and the cumulative price then
+		TwapStart get(twap_start): (T::BlockNumber, u128);
+		// Time-weighted average price over the latest complete window
+		Twap get(twap): u128;
+		// Blocks between solvency reports, zero to disable
+		SolvencyReportPeriod get(solvency_report_period): T::BlockNumber;
+
+		// Number of trades made, which is also the identifier of the next trade
+		TradeCount get(trade_count): u64;
+		// Currency paid for all tokens bought
+		CumulativeBuyVolume get(cumulative_buy_volume): u128;
+		// Currency returned for all tokens sold
+		CumulativeSellVolume get(cumulative_sell_volume): u128;
+		// Number of recent trades kept in the history, zero to disable
+		TradeHistorySize get(trade_history_size): u32;
+		// Ring buffer of recent trades by slot
+		TradeHistory get(trade_history): map u32 => Option<TradeRecord<T::Hash, T::BlockNumber, T::Moment>>;
+		// Slot the next trade is written to
+		TradeHistoryCursor get(trade_history_cursor): u32;
+		// Blocks in one candle window, zero to disable candles
+		CandleLength get(candle_length): T::BlockNumber;
+		// Number of candle windows kept, the latest included
+		CandleRetention get(candle_retention): u64;
+		// Price candles by window index
+		Candles get(candle): map u64 => Option<Candle>;
+		// Index of the latest window with a candle
+		LatestCandle get(latest_candle): u64;
+		// Age in blocks past which trade history and candles can be pruned, zero to
+		// keep them until they are overwritten or fall out of retention
+		HistoryHorizon get(history_horizon): T::BlockNumber;
+		// Trade history slot the next `prune` starts looking at
+		PruneCursor get(prune_cursor): u32;
+
+		// Identifier of the latest snapshot, zero before the first one
+		SnapshotId get(snapshot_id): u32;
+		// Balances of an account as they were at each snapshot it changed after
+		BalanceCheckpoints get(balance_checkpoints): map T::AccountId => Vec<(u32, u128)>;
+		// Total supply as it was at each snapshot it changed after
+		SupplyCheckpoints get(supply_checkpoints): Vec<(u32, u128)>;
+
+		// Identifier for the next distribution
+		NextDistributionId get(next_distribution_id): u32;
+		// Posted distributions by identifier
+		Distributions get(distribution): map u32 => Option<Distribution<T::Hash>>;
+		// Whether an account has claimed its share of a distribution
+		Claimed get(claimed): map (u32, T::AccountId) => bool;
+
+		// Account that may govern the token alongside root
+		Admin get(admin): Option<T::AccountId>;
+		// Whether governance was renounced, locking every parameter for good
+		AdminRenounced get(admin_renounced): bool;
+		// Blocks a parameter update waits in the queue before it applies, zero to allow
+		// updates to apply immediately
+		UpdateDelay get(update_delay) config(): T::BlockNumber;
+		// Identifier for the next queued update
+		NextUpdateId get(next_update_id): u64;
+		// Queued parameter updates by identifier, with the block they apply at and the
+		// admin that queued them, `None` for root
+		PendingUpdates get(pending_update): map u64 => Option<(T::BlockNumber, Call<T>, Option<T::AccountId>)>;
+		// Queued updates due at a given block
+		UpdatesDue get(updates_due): map T::BlockNumber => Vec<u64>;
+		// Set while a queued update is applied, letting it past the timelock
+		ApplyingUpdate get(applying_update): bool;
+		// Share of the supply whose vetoes cancel a queued update, in basis points, zero
+		// to disable vetoes
+		VetoThreshold get(veto_threshold): u32;
+		// Votes cast by vetoes of each queued update
+		VetoTally get(veto_tally): map u64 => u128;
+		// How vetoes of each category of update are tallied
+		TallyModes get(tally_mode): map UpdateCategory => TallyMode;
+		// How vetoes of each queued update are tallied, fixed when it was queued
+		UpdateTallyMode get(update_tally_mode): map u64 => TallyMode;
+		// Tokens an account has locked behind its veto of a queued update
+		Vetoes get(veto_of): map (u64, T::AccountId) => Option<Vote<T::BlockNumber>>;
+		// Voting power delegated to an account by others
+		DelegatedPower get(delegated_power): map T::AccountId => u128;
+		// Delegated power an account has locked behind vetoes
+		DelegatedPowerLocked get(delegated_power_locked): map T::AccountId => u128;
+		// Blocks in one vote lock era, the unit conviction locks are counted in
+		VoteLockEra get(vote_lock_era): T::BlockNumber;
+		// Identifier for the next stream
+		NextStreamId get(next_stream_id): u64;
+		// Open streams by identifier
+		Streams get(stream): map u64 => Option<Stream<T::AccountId, T::BlockNumber>>;
+		// Identifier for the next escrow
+		NextEscrowId get(next_escrow_id): u64;
+		// Open escrows by identifier
+		Escrows get(escrows): map u64 => Option<Escrow<T::AccountId, T::BlockNumber>>;
+		// Identifier for the next transfer waiting for a cosigner
+		NextPendingTransferId get(next_pending_transfer_id): u64;
+		// Transfers waiting for a cosigner by identifier
+		PendingTransfers get(pending_transfer): map u64 => Option<PendingTransfer<T::AccountId, T::Hash, T::BlockNumber>>;
+		// Identifier for the next reversible transfer
+		NextReversibleTransferId get(next_reversible_transfer_id): u64;
+		// Reversible transfers still in their dispute window by identifier
+		ReversibleTransfers get(reversible_transfer): map u64 => Option<ReversibleTransfer<T::AccountId, T::Hash, T::BlockNumber>>;
+		// Reversible transfers completing at a given block
+		ReversibleTransfersDue get(reversible_transfers_due): map T::BlockNumber => Vec<u64>;
+		// Hashed timelocks by the hash their preimage must match
+		Htlcs get(htlc): map T::Hash => Option<Htlc<T::AccountId, T::BlockNumber>>;
+		// Tokens taken out of balances and held by streams, escrows, hashed timelocks,
+		// transfers waiting for a cosigner and reversible transfers until paid out or
+		// refunded
+		EscrowedTokens get(escrowed_tokens): u128;
+		// Flash minted tokens not yet burned, which are held but not part of the supply
+		FlashMinted get(flash_minted): u128;
+		// Most tokens a single flash mint may create, zero to disable flash mints
+		FlashMintLimit get(flash_mint_limit): u128;
+		// Fee for a flash mint in basis points of the minted tokens' value at the spot
+		// price, paid into the funding pool
+		FlashMintFee get(flash_mint_fee): u32;
+
+		// Account that initialized the token, the beneficiary of transfer royalties
+		Creator get(creator): Option<T::AccountId>;
+		// Content-addressed URI of the token's metadata, e.g. an IPFS link to its image
+		// and description
+		MetadataUri get(metadata_uri): Option<Vec<u8>>;
+		// Royalty charged to the sender of a transfer on top of the value, in basis points
+		TransferRoyalty get(transfer_royalty): u32;
+		// Royalty tokens held for the creator until claimed
+		RoyaltiesAccrued get(royalties_accrued): u128;
+		// Whether entry and exit tributes are charged; off until governance enables them
+		FeesEnabled get(fees_enabled): bool;
+		// Entry tribute charged on top of the curve cost of a buy, in basis points
+		EntryFee get(entry_fee): u32;
+		// Currency collected from flash mint fees, and from tributes when the fee
+		// handler is this module, that backs tokens minted outside the curve
+		FundingPool get(funding_pool): T::Balance;
+		// Share of every tribute set aside for the insurance fund, in basis points
+		InsuranceShare get(insurance_share): u32;
+		// Currency set aside from tributes to top up the reserve if it becomes insolvent
+		InsuranceFund get(insurance_fund): T::Balance;
+		// Share of every tribute owed to the creator, in basis points
+		CreatorShare get(creator_share): u32;
+		// Tribute currency owed to the creator until claimed
+		CreatorFees get(creator_fees): T::Balance;
+		// Accounts tributes are split between, with their weights
+		Beneficiaries get(beneficiaries): Vec<(T::AccountId, u32)>;
+		// Tribute shares a beneficiary has been allotted but not yet claimed
+		BeneficiaryShares get(beneficiary_shares): map T::AccountId => u128;
+		// Share of the entry tribute paid to the referrer of a buy, in basis points
+		ReferralReward get(referral_reward): u32;
+		// Cumulative referral rewards earned by an account
+		ReferralEarnings get(referral_earnings): map T::AccountId => u128;
+		// Referral rewards an account has earned but not yet claimed
+		ReferralRewards get(referral_rewards): map T::AccountId => u128;
+		// Exit tribute taken from the curve return of a sell, in basis points
+		ExitFee get(exit_fee): u32;
+		// Surcharges on top of the entry and exit tributes for large trades, as
+		// (share of supply, surcharge) pairs in basis points
+		FeeTiers get(fee_tiers): Vec<(u32, u32)>;
+		// Holding-duration exit tribute schedule, as (blocks held, exit tribute) pairs in
+		// basis points; the longest duration an account reaches caps its exit tribute
+		LoyaltyTiers get(loyalty_tiers): Vec<(T::BlockNumber, u32)>;
+
+		// Spot price below which sells are rejected
+		MinPrice get(min_price): Option<u128>;
+		// Spot price above which buys are rejected
+		MaxPrice get(max_price): Option<u128>;
+
+		// Most tokens a single account may buy in total
+		PurchaseCap get(purchase_cap): Option<u128>;
+
+		// Blocks an account must wait after buying before it can sell
+		SellCooldown get(sell_cooldown): T::BlockNumber;
+
+		// Blocks bought tokens stay unsellable, zero to disable
+		BuyLockPeriod get(buy_lock_period): T::BlockNumber;
+
+		// Locks, cost basis and other state of an account besides its balance
+		Accounts get(account) build(|config: &GenesisConfig<T>| {
+			if !T::TRACK_COST_BASIS {
+				return Vec::new();
+			}
+			presale_spent(&config.presale).into_iter()
+				.map(|(who, spent)| (who, AccountData { total_spent: spent, ..Default::default() }))
+				.collect::<Vec<_>>()
+		}): map T::AccountId => AccountData<T::AccountId, T::BlockNumber>;
+	}
+	add_extra_genesis {
+		// Presale allocations minted at genesis, as (account, tokens, price paid per token)
+		config(presale): Vec<(T::AccountId, u128, u128)>;
+	}
+	extra_genesis_skip_phantom_data_field;
+}
+
+/// Tokens allocated to each presale account, with repeated accounts summed and empty
+/// allocations dropped.
+#[cfg(feature = "std")]
+fn presale_balances<AccountId: Clone + PartialEq>(presale: &[(AccountId, u128, u128)]) -> Vec<(AccountId, u128)> {
+	presale_totals(presale.iter().map(|(who, amount, _)| (who, *amount)))
+}
+
+/// Currency each presale account paid for its allocation.
+#[cfg(feature = "std")]
+fn presale_spent<AccountId: Clone + PartialEq>(presale: &[(AccountId, u128, u128)]) -> Vec<(AccountId, u128)> {
+	presale_totals(presale.iter().map(|(who, amount, price)| (who, amount.saturating_mul(*price))))
+}
+
+#[cfg(feature = "std")]
+fn presale_totals<'a, AccountId: 'a + Clone + PartialEq>(
+	entries: impl Iterator<Item = (&'a AccountId, u128)>
+) -> Vec<(AccountId, u128)> {
+	let mut totals: Vec<(AccountId, u128)> = Vec::new();
+	for (who, value) in entries.filter(|(_, value)| *value > 0) {
+		match totals.iter_mut().find(|(account, _)| account == who) {
+			Some((_, total)) => *total = total.saturating_add(value),
+			None => totals.push((who.clone(), value)),
+		}
 	}
+	totals
 }
 
 decl_module! {
@@ -44,295 +1265,5082 @@ decl_module! {
 		// this is needed only if you are using events in your module
 		fn deposit_event<T>() = default;
 
-		pub fn transfer(origin, to: T::AccountId, value: u128) -> Result {
+		fn on_initialise(n: T::BlockNumber) {
+			if !Self::legacy_total_seeded() {
+				Self::_seed_legacy_total();
+			}
+			for id in <UpdatesDue<T>>::take(n) {
+				Self::_apply_update(id);
+			}
+			Self::_apply_curve_schedule(n);
+			if let Some(auction) = Self::auction() {
+				// The falling price can reach what has been committed between bids.
+				let sold_out = auction.price_at(n).saturating_mul(auction.tranche) <= auction.committed;
+				if auction.clearing_price.is_none() && (n >= auction.end || sold_out) {
+					Self::_close_auction(auction, n);
+				}
+			}
+			if n <= Self::launch_end() {
+				<SpotPrice<T>>::put(Self::price_at(Self::total_supply()));
+			}
+			Self::_honor_payouts();
+			for id in <RecurringBuysDue<T>>::take(n) {
+				Self::_execute_recurring_buy(id, n);
+			}
+			for id in <ReversibleTransfersDue<T>>::take(n) {
+				Self::_finalize_reversible_transfer(id);
+			}
+			Self::_check_invariants(&[]);
+		}
+
+		fn on_finalise(n: T::BlockNumber) {
+			Self::_settle_orders();
+			if Self::is_init() {
+				Self::_feed_price(n);
+			}
+
+			let period = Self::stats_period();
+			if !period.is_zero() && (n % period).is_zero() {
+				Self::_publish_stats(n);
+			}
+
+			let period = Self::solvency_report_period();
+			if !period.is_zero() && (n % period).is_zero() {
+				Self::_report_solvency();
+			}
+
+			let era = Self::era_length();
+			if !era.is_zero() && (n % era).is_zero() {
+				Self::_mint_inflation();
+				Self::_tune_slope();
+			}
+
+			if !Self::paused() {
+				if let Err(e) = Self::audit_solvency() {
+					<Paused<T>>::put(n);
+					Self::deposit_event(RawEvent::EmergencyPaused(e.as_bytes().to_vec()));
+				}
+			}
+			Self::_check_invariants(&[]);
+		}
+
+		pub fn transfer(origin, to: <T::Lookup as StaticLookup>::Source, #[compact] value: u128) -> Result {
 			let sender = ensure_signed(origin)?;
-			Self::_transfer(sender, to, value)
+			let to = T::Lookup::lookup(to)?;
+			let royalty = Self::_royalty(&sender, value);
+			let result = Self::_send(sender, to, value, royalty, Outflow::Transfer);
+			Self::_check_invariants(&[]);
+			result
 		}
 
-		pub fn approve(origin, spender: T::AccountId, value: u128) -> Result {
+		/// Transfers `value` tokens to `to` and hands `data` to the configured
+		/// `OnTokenReceived` handler, so a deposit and the action it funds happen together.
+		pub fn transfer_and_call(origin, to: <T::Lookup as StaticLookup>::Source, #[compact] value: u128, data: Vec<u8>) -> Result {
 			let sender = ensure_signed(origin)?;
-			// Make sure the approver/owner owns this token
-			ensure!(<BalanceOf<T>>::exists(&sender), "Account does not own this token");
+			let to = T::Lookup::lookup(to)?;
+			Self::_ensure_not_held(&sender, value)?;
+			let royalty = Self::_royalty(&sender, value);
+			Self::_with_spend_limit(&sender, value, || Self::_transfer(sender.clone(), to.clone(), value, royalty))?;
 
-			// Get the current value of the allowance for this sender and spender
-			// combination. If it doesn't exist then default 0 will be returned.
-			let allowance = Self::allowance((sender.clone(), spender.clone()));
+			if let Err(e) = T::OnTokenReceived::on_token_received(&sender, &to, value, &data) {
+				Self::_transfer(to, sender.clone(), value, 0)?;
+				// Refund the royalty too, as the transfer never happened.
+				<RoyaltiesAccrued<T>>::mutate(|accrued| *accrued -= royalty);
+				Self::_set_balance(&sender, Self::balance_of(&sender) + royalty);
+				Self::_check_invariants(&[]);
+				return Err(e);
+			}
+			Self::_check_invariants(&[]);
+			Ok(())
+		}
 
-			// Add the value to the current allowance.
-			// Uses `checked_add` which is Safe Math to avoid overflows.
-			let updated_allowance = allowance.checked_add(value).ok_or("overflow in calculating allowance")?;
+		/// Escrows `rate_per_block * duration` tokens that `to` can withdraw as they are
+		/// released, `rate_per_block` a block from now.
+		pub fn open_stream(origin, to: T::AccountId, #[compact] rate_per_block: u128, duration: T::BlockNumber) -> Result {
+			let sender = ensure_signed(origin)?;
+			ensure!(sender != to, "Cannot stream to yourself.");
+			ensure!(rate_per_block > 0, "Stream rate must be positive.");
+			ensure!(!duration.is_zero(), "Stream must last at least one block.");
+			let blocks: u64 = duration.as_();
+			let amount = rate_per_block.checked_mul(blocks as u128).ok_or("Overflow in calculating stream total.")?;
 
-			// Insert the new allowance value of this sender and spender combination.
-			<Allowance<T>>::insert((sender.clone(), spender.clone()), updated_allowance);
+			let result = Self::_send(sender, to, amount, 0, Outflow::Stream(rate_per_block, duration));
+			Self::_check_invariants(&[]);
+			result
+		}
 
-			// Bubble up the Approval event.
-			Self::deposit_event(RawEvent::Approval(sender, spender, value));
+		/// Pays the recipient of a stream the tokens released so far.
+		pub fn withdraw_stream(origin, id: u64) -> Result {
+			let sender = ensure_signed(origin)?;
+			let mut stream = Self::stream(id).ok_or("Stream does not exist.")?;
+			ensure!(stream.to == sender, "Not the recipient of this stream.");
+			let now = <system::Module<T>>::block_number();
+			let amount = stream.released_at(now) - stream.withdrawn;
+			ensure!(amount > 0, "Nothing to withdraw yet.");
+
+			Self::_release(&sender, amount);
+			stream.withdrawn += amount;
+			if now >= stream.end {
+				<Streams<T>>::remove(id);
+			} else {
+				<Streams<T>>::insert(id, stream);
+			}
+
+			Self::deposit_event(RawEvent::StreamWithdrawn(id, sender, amount));
+			Self::_check_invariants(&[]);
 			Ok(())
 		}
 
-		pub fn transfer_from(_origin, from: T::AccountId, to: T::AccountId, value: u128) -> Result {
-			ensure!(<Allowance<T>>::exists((from.clone(), to.clone())), "Allowance does not exist.");
-			// This allowance works differently than in Ethereum.
-			let allowance = Self::allowance((from.clone(), to.clone()));
-			ensure!(allowance >= value, "Not enough allowance.");
+		/// Closes a stream, paying the recipient what has been released and refunding the
+		/// rest to the sender. Either party can cancel.
+		pub fn cancel_stream(origin, id: u64) -> Result {
+			let sender = ensure_signed(origin)?;
+			let stream = Self::stream(id).ok_or("Stream does not exist.")?;
+			ensure!(stream.from == sender || stream.to == sender, "Not a party to this stream.");
+			let now = <system::Module<T>>::block_number();
+			let released = stream.released_at(now);
+			let total = stream.released_at(stream.end);
 
-			// Uses `checked_sub` to avoid underflows.
-			let updated_allowance = allowance.checked_sub(value).ok_or("Underflow in allowance calculation.")?;
+			Self::_release(&stream.to, released - stream.withdrawn);
+			Self::_release(&stream.from, total - released);
+			<Streams<T>>::remove(id);
 
-			// Insert the new allowance value of this sender and spender combination.
-			<Allowance<T>>::insert((from.clone(), to.clone()), updated_allowance);
+			Self::deposit_event(RawEvent::StreamCancelled(id, released - stream.withdrawn, total - released));
+			Self::_check_invariants(&[]);
+			Ok(())
+		}
 
-			Self::deposit_event(RawEvent::Approval(from.clone(), to.clone(), value));
-			Self::_transfer(from, to, value)
+		/// Holds `amount` tokens for `to` until `arbiter` releases or refunds them. The
+		/// funder can also release them and the recipient can refund them, and after
+		/// `deadline` the funder can take them back.
+		pub fn escrow(origin, to: T::AccountId, arbiter: T::AccountId, #[compact] amount: u128, deadline: T::BlockNumber) -> Result {
+			let sender = ensure_signed(origin)?;
+			ensure!(sender != to, "Cannot escrow to yourself.");
+			ensure!(amount > 0, "Escrow must hold some tokens.");
+
+			let result = Self::_send(sender, to, amount, 0, Outflow::Escrow(arbiter, deadline));
+			Self::_check_invariants(&[]);
+			result
 		}
 
-		pub fn buy(origin, tokens: u128) -> Result {
+		/// Releases an escrow to its recipient. Allowed to the arbiter and the funder.
+		pub fn release_escrow(origin, id: u64) -> Result {
 			let sender = ensure_signed(origin)?;
+			let escrow = Self::escrows(id).ok_or("Escrow does not exist.")?;
+			ensure!(sender == escrow.arbiter || sender == escrow.from, "Not allowed to release this escrow.");
 
-			let supply = Self::total_supply(); 
+			<Escrows<T>>::remove(id);
+			Self::_release(&escrow.to, escrow.amount);
 
-			let new_supply = match supply.checked_add(tokens) {
-				Some(x) => x,
-				None => return Err("Overflow while buying tokens."),
-			};
+			Self::deposit_event(RawEvent::EscrowReleased(id, escrow.to, escrow.amount));
+			Self::_check_invariants(&[]);
+			Ok(())
+		}
 
-			let integral_before = Self::_integral(supply);
-			let integral_after = Self::_integral(new_supply);
+		/// Refunds an escrow to its funder. Allowed to the arbiter and the recipient, and
+		/// to the funder once the deadline has passed.
+		pub fn refund_escrow(origin, id: u64) -> Result {
+			let sender = ensure_signed(origin)?;
+			let escrow = Self::escrows(id).ok_or("Escrow does not exist.")?;
+			let timed_out = <system::Module<T>>::block_number() > escrow.deadline;
+			ensure!(
+				sender == escrow.arbiter || sender == escrow.to || (sender == escrow.from && timed_out),
+				"Not allowed to refund this escrow."
+			);
 
-			let cost = integral_after - integral_before;
-			let cost_ = <T::Balance>::sa(cost.as_());
+			<Escrows<T>>::remove(id);
+			Self::_release(&escrow.from, escrow.amount);
 
-			<balances::Module<T>>::decrease_free_balance(&sender, cost_)?;
-			<Reserve<T>>::mutate(|reserve| *reserve += cost_);
+			Self::deposit_event(RawEvent::EscrowRefunded(id, escrow.from, escrow.amount));
+			Self::_check_invariants(&[]);
+			Ok(())
+		}
 
-			Self::_mint(sender.clone(), tokens)?;
+		/// Requires `cosigner` to approve transfers, escrows, streams and hashed timelocks
+		/// by the sender above `threshold`. Once set, the policy can only be removed by the
+		/// cosigner.
+		pub fn set_cosigner(origin, cosigner: T::AccountId, #[compact] threshold: u128) -> Result {
+			let sender = ensure_signed(origin)?;
+			ensure!(sender != cosigner, "Cannot cosign your own transfers.");
+			ensure!(Self::cosigner(&sender).is_none(), "The current cosigner must remove its policy first.");
 
-			Self::deposit_event(RawEvent::Buy(Some(sender), tokens, cost));
+			Self::_mutate_account(&sender, |account| account.cosigner = Some((cosigner.clone(), threshold)));
 
+			Self::deposit_event(RawEvent::CosignerSet(sender, cosigner, threshold));
 			Ok(())
 		}
 
-		pub fn sell(origin, tokens: u128) -> Result {
+		/// Removes the cosigning policy of `who`. Only allowed to its cosigner.
+		pub fn remove_cosigner(origin, who: T::AccountId) -> Result {
 			let sender = ensure_signed(origin)?;
+			let (cosigner, _) = Self::cosigner(&who).ok_or("Account has no cosigner.")?;
+			ensure!(sender == cosigner, "Only the cosigner can remove its policy.");
 
-			let supply = Self::total_supply();
+			Self::_mutate_account(&who, |account| account.cosigner = None);
 
-			let new_supply = match supply.checked_sub(tokens) {
-				Some(x) => x,
-				None => return Err("Underflow while selling tokens.")
-			};
+			Self::deposit_event(RawEvent::CosignerRemoved(who));
+			Ok(())
+		}
 
-			let integral_before = Self::_integral(supply);
-			let integral_after = Self::_integral(new_supply);
+		/// Limits the tokens the sender may transfer, escrow, stream, lock, sell or redeem
+		/// to `amount` every `window` blocks. A limit already set can only be tightened; loosening it means lifting
+		/// it first.
+		pub fn set_spend_limit(origin, #[compact] amount: u128, window: T::BlockNumber) -> Result {
+			let sender = ensure_signed(origin)?;
+			ensure!(!window.is_zero(), "Spend limit window must be at least one block.");
+			let now = <system::Module<T>>::block_number();
+			let current = Self::_spend_limit_after(&sender, 0)?;
+			if let Some(ref current) = current {
+				ensure!(!current.imposed, "Spend limit was imposed by the admin.");
+				ensure!(amount <= current.amount && window >= current.window, "Spend limit can only be tightened.");
+			}
+			let (window_start, spent) = current.map_or((now, 0), |current| (current.window_start, current.spent));
 
-			let ret_amount = integral_before - integral_after;
-			let ret_amount_ = <T::Balance>::sa(ret_amount.as_());
+			Self::_mutate_account(&sender, |account| account.spend_limit = Some(SpendLimit {
+				amount, window, window_start, spent, imposed: false, lifts_at: None,
+			}));
 
-			<Reserve<T>>::mutate(|reserve| *reserve -= ret_amount_);
-			<balances::Module<T>>::increase_free_balance_creating(&sender, ret_amount_);
+			Self::deposit_event(RawEvent::SpendLimitSet(sender, amount, window));
+			Ok(())
+		}
 
-			Self::_burn(sender.clone(), tokens)?;
+		/// Lifts the sender's own spend limit one window from now, so a stolen key cannot
+		/// lift it and empty the account at once.
+		pub fn lift_spend_limit(origin) -> Result {
+			let sender = ensure_signed(origin)?;
+			let mut limit = Self::spend_limit(&sender).ok_or("Account has no spend limit.")?;
+			ensure!(!limit.imposed, "Spend limit was imposed by the admin.");
+			ensure!(limit.lifts_at.is_none(), "Spend limit is already being lifted.");
+			let lifts_at = <system::Module<T>>::block_number() + limit.window;
+			limit.lifts_at = Some(lifts_at);
 
-			Self::deposit_event(RawEvent::Sell(Some(sender), tokens, ret_amount));
+			Self::_mutate_account(&sender, |account| account.spend_limit = Some(limit));
 
+			Self::deposit_event(RawEvent::SpendLimitLifting(sender, lifts_at));
 			Ok(())
 		}
 
-		/// Initializes the token with constructor parameters.
-		pub fn init(_origin, exp: u128, slp: u128) -> Result {
-			ensure!(
-				!Self::is_init(),
-				"Token is already initialized!"
-			);
-
-			<Exponent<T>>::put(exp);
-			<Slope<T>>::put(slp);
+		/// Imposes a limit of `amount` tokens every `window` blocks on what `who` may
+		/// transfer or sell, replacing any limit it set itself, or removes the limit with
+		/// `None`.
+		pub fn impose_spend_limit(origin, who: T::AccountId, limit: Option<(u128, T::BlockNumber)>) -> Result {
+			Self::_ensure_admin(origin)?;
+			let limit = match limit {
+				Some((amount, window)) => {
+					ensure!(!window.is_zero(), "Spend limit window must be at least one block.");
+					let now = <system::Module<T>>::block_number();
+					Some(SpendLimit { amount, window, window_start: now, spent: 0, imposed: true, lifts_at: None })
+				},
+				None => None,
+			};
 
-			<Init<T>>::put(true);
+			Self::_mutate_account(&who, |account| account.spend_limit = limit.clone());
 
+			match limit {
+				Some(limit) => Self::deposit_event(RawEvent::SpendLimitSet(who, limit.amount, limit.window)),
+				None => Self::deposit_event(RawEvent::SpendLimitRemoved(who)),
+			}
 			Ok(())
 		}
 
-		/// Test function to create some tokens.
-		pub fn create_tokens(origin, amount: u128) -> Result {
+		/// Holds the sender's transfers, escrows, streams and hashed timelocks for
+		/// `period` blocks, during which the sender or `guardian` can reverse them, or
+		/// turns this off with `None`.
+		pub fn set_reversible_transfers(origin, mode: Option<(T::AccountId, T::BlockNumber)>) -> Result {
 			let sender = ensure_signed(origin)?;
+			if let Some((ref guardian, ref period)) = mode {
+				ensure!(*guardian != sender, "Cannot guard your own transfers.");
+				ensure!(!period.is_zero(), "Transfers must be held for at least one block.");
+			}
+
+			Self::_mutate_account(&sender, |account| account.reversible = mode.clone());
 
-			Self::_mint(sender, amount)?;
+			Self::deposit_event(RawEvent::ReversibleTransfersSet(sender, mode.map(|(guardian, _)| guardian)));
 			Ok(())
 		}
 
-		/// Test function to clear the storage.
-		pub fn clear_storage(origin) -> Result {
+		/// Returns a reversible transfer to its sender before its window ends. Allowed to
+		/// the sender and the guardian.
+		pub fn reverse_transfer(origin, id: u64) -> Result {
 			let sender = ensure_signed(origin)?;
+			let transfer = Self::reversible_transfer(id).ok_or("Reversible transfer does not exist.")?;
+			ensure!(sender == transfer.from || sender == transfer.guardian, "Not allowed to reverse this transfer.");
 
-			<TotalSupply<T>>::put(0);
-			<BalanceOf<T>>::remove(&sender);
-			<Reserve<T>>::put(<T::Balance>::sa(0));
+			<ReversibleTransfers<T>>::remove(id);
+			<ReversibleTransfersDue<T>>::mutate(transfer.finalizes_at, |ids| ids.retain(|due| *due != id));
+			Self::_release(&transfer.from, transfer.amount);
 
+			Self::deposit_event(RawEvent::TransferReversed(id));
+			Self::_check_invariants(&[]);
 			Ok(())
 		}
-	}
-}
 
-decl_event!(
-	/// An event in this module.
-	pub enum Event<T> where AccountId = <T as system::Trait>::AccountId {
+		/// Completes a transfer waiting for the sender's cosigner. Only allowed to the
+		/// cosigner.
+		pub fn approve_transfer(origin, id: u64) -> Result {
+			let sender = ensure_signed(origin)?;
+			let pending = Self::pending_transfer(id).ok_or("Pending transfer does not exist.")?;
+			ensure!(sender == pending.cosigner, "Only the cosigner can approve this transfer.");
+
+			<PendingTransfers<T>>::remove(id);
+			Self::deposit_event(RawEvent::TransferApproved(id));
+			if !Self::_complete(pending.from, pending.to, pending.amount, pending.outflow) {
+				Self::deposit_event(RawEvent::TransferCancelled(id));
+			}
+			Self::_check_invariants(&[]);
+			Ok(())
+		}
+
+		/// Returns a transfer waiting for a cosigner to its sender. The royalty charged
+		/// when it was sent is kept. Allowed to the sender and the cosigner.
+		pub fn cancel_transfer(origin, id: u64) -> Result {
+			let sender = ensure_signed(origin)?;
+			let pending = Self::pending_transfer(id).ok_or("Pending transfer does not exist.")?;
+			ensure!(sender == pending.from || sender == pending.cosigner, "Not allowed to cancel this transfer.");
+
+			<PendingTransfers<T>>::remove(id);
+			Self::_release(&pending.from, pending.amount);
+
+			Self::deposit_event(RawEvent::TransferCancelled(id));
+			Self::_check_invariants(&[]);
+			Ok(())
+		}
+
+		/// Locks `amount` tokens for `to` under `hash`, for one side of an atomic swap.
+		/// The tokens go to `to` when the preimage of `hash` is revealed before
+		/// `timeout`, and can be refunded from then on.
+		pub fn htlc_lock(origin, hash: T::Hash, to: T::AccountId, #[compact] amount: u128, timeout: T::BlockNumber) -> Result {
+			let sender = ensure_signed(origin)?;
+			ensure!(amount > 0, "Hashed timelock must lock some tokens.");
+
+			let result = Self::_send(sender, to, amount, 0, Outflow::Htlc(hash, timeout));
+			Self::_check_invariants(&[]);
+			result
+		}
+
+		/// Pays out the hashed timelock matching `preimage` to its recipient. Anyone
+		/// holding the preimage can claim, which reveals it to the other chain.
+		pub fn htlc_claim(origin, preimage: Vec<u8>) -> Result {
+			let _ = ensure_signed(origin)?;
+			ensure!(preimage.len() <= MAX_PREIMAGE_LEN, "Preimage is too long.");
+			let hash = T::Hashing::hash(&preimage);
+			let htlc = Self::htlc(hash).ok_or("No hashed timelock for this preimage.")?;
+			ensure!(<system::Module<T>>::block_number() < htlc.timeout, "Hashed timelock has timed out.");
+
+			<Htlcs<T>>::remove(hash);
+			Self::_release(&htlc.to, htlc.amount);
+
+			Self::deposit_event(RawEvent::HtlcClaimed(hash, preimage));
+			Self::_check_invariants(&[]);
+			Ok(())
+		}
+
+		/// Returns the tokens of a timed out hashed timelock to whoever locked them.
+		pub fn htlc_refund(origin, hash: T::Hash) -> Result {
+			let _ = ensure_signed(origin)?;
+			let htlc = Self::htlc(hash).ok_or("No hashed timelock for this hash.")?;
+			ensure!(<system::Module<T>>::block_number() >= htlc.timeout, "Hashed timelock has not timed out.");
+
+			<Htlcs<T>>::remove(hash);
+			Self::_release(&htlc.from, htlc.amount);
+
+			Self::deposit_event(RawEvent::HtlcRefunded(hash, htlc.from, htlc.amount));
+			Self::_check_invariants(&[]);
+			Ok(())
+		}
+
+		/// Transfers `amount` tokens to `to` locked under a linear vesting schedule that
+		/// releases `per_block` tokens a block from `start_block`.
+		pub fn transfer_vested(origin, to: T::AccountId, #[compact] amount: u128, #[compact] per_block: u128, start_block: T::BlockNumber) -> Result {
+			let sender = ensure_signed(origin)?;
+			ensure!(per_block > 0, "Vesting must release tokens every block.");
+			Self::_ensure_not_held(&sender, amount)?;
+
+			let now = <system::Module<T>>::block_number();
+			let mut schedules: Vec<_> = Self::vesting(&to).into_iter().filter(|v| v.locked_at(now) > 0).collect();
+			ensure!(schedules.len() < MAX_VESTING_SCHEDULES, "Too many vesting schedules.");
+
+			Self::_with_spend_limit(&sender, amount, || Self::_transfer(sender.clone(), to.clone(), amount, Self::_royalty(&sender, amount)))?;
+
+			schedules.push(VestingSchedule { locked: amount, per_block, start: start_block, cliff: start_block });
+			Self::_mutate_account(&to, |account| account.vesting = schedules);
+
+			Self::deposit_event(RawEvent::VestedTransfer(sender, to, amount));
+			Self::_check_invariants(&[]);
+			Ok(())
+		}
+
+		pub fn approve(origin, spender: <T::Lookup as StaticLookup>::Source, #[compact] value: u128) -> Result {
+			let sender = ensure_signed(origin)?;
+			let spender = T::Lookup::lookup(spender)?;
+			// Make sure the approver/owner owns this token
+			ensure!(Self::_has_balance_entry(&sender), "Account does not own this token");
+
+			// Get the current value of the allowance for this sender and spender
+			// combination. If it doesn't exist then default 0 will be returned.
+			let allowance = Self::allowance((sender.clone(), spender.clone()));
+
+			// Add the value to the current allowance.
+			// Uses `checked_add` which is Safe Math to avoid overflows.
+			let updated_allowance = allowance.checked_add(value).ok_or("overflow in calculating allowance")?;
+
+			// A new spender is indexed and pays a deposit, bounding the allowances an
+			// owner can accumulate.
+			let mut spenders = Self::spenders(&sender);
+			if !spenders.iter().any(|(s, _)| *s == spender) {
+				ensure!((spenders.len() as u32) < T::MAX_ALLOWANCES, "Too many allowances.");
+				let deposit = Self::allowance_deposit();
+				<balances::Module<T>>::reserve(&sender, deposit)?;
+				spenders.push((spender.clone(), deposit));
+				<Spenders<T>>::insert(&sender, spenders);
+			}
+
+			// Insert the new allowance value of this sender and spender combination.
+			Self::_set_allowance((sender.clone(), spender.clone()), Some(updated_allowance));
+
+			// Bubble up the Approval event.
+			Self::_check_invariants(&[&sender]);
+			Self::deposit_event(RawEvent::Approval(sender, spender, value));
+			Ok(())
+		}
+
+		pub fn transfer_from(
+			origin,
+			from: <T::Lookup as StaticLookup>::Source,
+			to: <T::Lookup as StaticLookup>::Source,
+			#[compact] value: u128
+		) -> Result {
+			let sender = ensure_signed(origin)?;
+			let from = T::Lookup::lookup(from)?;
+			let to = T::Lookup::lookup(to)?;
+			// The allowance is the spender's, and the tokens go to the spender.
+			ensure!(sender == to, "Only the spender can use its allowance.");
+			ensure!(Self::_has_allowance_entry((from.clone(), to.clone())), "Allowance does not exist.");
+			// This allowance works differently than in Ethereum.
+			let allowance = Self::allowance((from.clone(), to.clone()));
+			ensure!(allowance >= value, "Not enough allowance.");
+
+			// Uses `checked_sub` to avoid underflows.
+			let updated_allowance = allowance.checked_sub(value).ok_or("Underflow in allowance calculation.")?;
+
+			// Transfer first so a failed transfer does not consume the allowance.
+			Self::_send(from.clone(), to.clone(), value, Self::_royalty(&from, value), Outflow::Transfer)?;
+
+			// Insert the new allowance value of this sender and spender combination.
+			Self::_set_allowance((from.clone(), to.clone()), Some(updated_allowance));
+
+			Self::_check_invariants(&[&from]);
+			Self::deposit_event(RawEvent::Approval(from, to, value));
+			Ok(())
+		}
+
+		pub fn buy(origin, #[compact] tokens: u128, referrer: Option<T::AccountId>) -> Result {
+			let sender = ensure_signed(origin)?;
+			let result = Self::_buy(sender.clone(), sender, tokens, None, referrer);
+			Self::_check_invariants(&[]);
+			result
+		}
+
+		/// Buys `tokens` paid for by the sender but minted to `beneficiary`, failing if
+		/// the cost including the entry tribute exceeds `max_cost`.
+		pub fn buy_for(origin, beneficiary: T::AccountId, #[compact] tokens: u128, #[compact] max_cost: u128) -> Result {
+			let sender = ensure_signed(origin)?;
+			let result = Self::_buy(sender, beneficiary, tokens, Some(max_cost), None);
+			Self::_check_invariants(&[]);
+			result
+		}
+
+		/// Sells `tokens` back to the curve, paying the proceeds to `payout_to` if given
+		/// and to the seller otherwise.
+		pub fn sell(origin, #[compact] tokens: u128, payout_to: Option<T::AccountId>) -> Result {
+			let sender = ensure_signed(origin)?;
+			let payee = payout_to.unwrap_or_else(|| sender.clone());
+			let result = Self::_with_spend_limit(&sender, tokens, || Self::_sell(sender.clone(), payee, tokens));
+			Self::_check_invariants(&[]);
+			result
+		}
+
+		/// Mints `amount` tokens to the sender and hands them with `data` to the
+		/// configured `FlashBorrower`. The tokens are burned and the fee kept when it
+		/// returns.
+		///
+		/// Flash minted tokens are not added to the supply, so selling them on the curve
+		/// pays out only what the existing supply is owed at the top of the curve, and the
+		/// curve takes no buys until they are burned. Storage is not rolled back when a
+		/// dispatch fails, so the curve cost of the tokens is taken from the sender as
+		/// collateral along with the fee. Tokens left unpaid stay in the supply, backed
+		/// from the collateral, and the rest of the collateral is refunded.
+		pub fn flash_mint(origin, #[compact] amount: u128, data: Vec<u8>) -> Result {
+			let sender = ensure_signed(origin)?;
+			ensure!(Self::is_init(), "Token is not initialized.");
+			ensure!(amount > 0, "Flash mint must be positive.");
+			ensure!(amount <= Self::flash_mint_limit(), "Flash mint is above the limit.");
+			ensure!(Self::flash_minted() == 0, "A flash mint is already in progress.");
+			let fee = Self::_bps(Self::spot_price().saturating_mul(amount), Self::flash_mint_fee());
+			let fee_ = Self::_to_balance(fee)?;
+			let collateral = Self::cost_to_buy(amount).ok_or("Flash mint would exceed the maximum supply.")?;
+			let collateral_ = Self::_to_balance(collateral)?;
+			Self::_withdraw(&sender, collateral_.checked_add(&fee_).ok_or("Overflow in flash mint collateral.")?)?;
+
+			<FlashMinted<T>>::put(amount);
+			Self::_set_balance(&sender, Self::balance_of(&sender) + amount);
+			Self::deposit_event(RawEvent::Transfer(None, Some(sender.clone()), amount));
+
+			let result = T::FlashBorrower::on_flash_mint(&sender, amount, fee, &data);
+
+			let free = Self::balance_of(&sender).saturating_sub(Self::_locked(&sender));
+			let repaid = cmp::min(free, amount);
+			Self::_set_balance(&sender, Self::balance_of(&sender) - repaid);
+			<FlashMinted<T>>::kill();
+			Self::deposit_event(RawEvent::Transfer(Some(sender.clone()), None, repaid));
+
+			if repaid < amount {
+				// The unpaid tokens left the account, so they join the supply and the
+				// collateral makes up whatever the reserve no longer covers.
+				Self::_set_supply(Self::_held_supply());
+				let owed = Self::curve_params().area(0, Self::_backed_supply(), false).unwrap_or(u128::max_value());
+				let kept = cmp::min(Self::_saturating_balance(owed.saturating_sub(Self::_total_reserve())), collateral_);
+				<Reserve<T>>::mutate(|reserve| *reserve += kept);
+				<FundingPool<T>>::mutate(|pool| *pool += fee_);
+				<balances::Module<T>>::increase_free_balance_creating(&sender, collateral_ - kept);
+				Self::deposit_event(RawEvent::FlashMintDefaulted(sender, amount - repaid, kept));
+				Self::_check_invariants(&[]);
+				return Err("Flash mint was not repaid.");
+			}
+
+			if result.is_ok() {
+				<FundingPool<T>>::mutate(|pool| *pool += fee_);
+				<balances::Module<T>>::increase_free_balance_creating(&sender, collateral_);
+				Self::deposit_event(RawEvent::FlashMintRepaid(sender, amount, fee_));
+			} else {
+				<balances::Module<T>>::increase_free_balance_creating(&sender, collateral_ + fee_);
+			}
+			Self::_check_invariants(&[]);
+			result
+		}
+
+		/// Sells `amount` tokens to this curve and spends the proceeds on the counterpart
+		/// token in one call, failing if that buys fewer than `min_out`.
+		pub fn convert(origin, #[compact] amount: u128, #[compact] min_out: u128) -> Result {
+			let sender = ensure_signed(origin)?;
+
+			let payout = Self::_sell_payout(Some(&sender), amount).ok_or("Underflow while selling tokens.")?;
+			let ret_amount = Self::return_on_sell(amount).ok_or("Underflow while selling tokens.")?;
+			ensure!(Self::_pays_now(Self::_to_balance(ret_amount)?), "Liquid reserve cannot cover the sale.");
+			// The sell cannot be undone once made, so the counterpart buy is checked first.
+			<T as balances::Trait>::EnsureAccountLiquid::ensure_account_liquid(&sender)?;
+			T::Counterpart::ensure_can_buy(&sender, payout, min_out)?;
+
+			Self::_with_spend_limit(&sender, amount, || Self::_sell(sender.clone(), sender.clone(), amount))?;
+			T::Counterpart::buy_with(&sender, payout, min_out)?;
+
+			Self::_check_invariants(&[]);
+			Ok(())
+		}
+
+		/// Mints into or burns from the rebalance buffer to move the spot price towards
+		/// the oracle price once they diverge by more than the threshold. Moves at most
+		/// the configured maximum per call. Minted tokens are backed from the funding
+		/// pool and the reserve behind burned ones returns to it. Anyone may call this.
+		pub fn rebalance(origin) -> Result {
+			let _ = ensure_signed(origin)?;
+			let buffer = Self::rebalance_buffer().ok_or("No rebalance buffer is configured.")?;
+			let oracle = Self::oracle_price().ok_or("Oracle price is unavailable.")?;
+
+			let supply = Self::total_supply();
+			let spot = Self::price_at(supply);
+			let gap = if oracle > spot { oracle - spot } else { spot - oracle };
+			ensure!(
+				gap.saturating_mul(BPS) > spot.saturating_mul(Self::rebalance_threshold() as u128),
+				"Spot price is within the rebalance threshold."
+			);
+
+			let target = Self::_supply_for_price(oracle);
+			let (minted, burned) = if target > supply {
+				(cmp::min(target - supply, Self::max_rebalance()), 0)
+			} else {
+				(0, cmp::min(cmp::min(supply - target, Self::max_rebalance()), Self::balance_of(&buffer)))
+			};
+			ensure!(minted > 0 || burned > 0, "Nothing to rebalance.");
+
+			if minted > 0 {
+				Self::_mint_backed(buffer, minted)?;
+			} else {
+				Self::_burn_backed(buffer, burned)?;
+			}
+
+			Self::deposit_event(RawEvent::Rebalanced(oracle, minted, burned));
+			Self::_check_invariants(&[]);
+			Ok(())
+		}
+
+		/// Sets the account rebalancing mints into and burns from, the divergence in
+		/// basis points that allows a rebalance and the most tokens one may move.
+		/// `None` disables rebalancing.
+		pub fn set_rebalance_config(origin, buffer: Option<T::AccountId>, threshold: u32, #[compact] max_rebalance: u128) -> Result {
+			Self::_ensure_admin(origin)?;
+			match buffer {
+				Some(buffer) => <RebalanceBuffer<T>>::put(buffer),
+				None => <RebalanceBuffer<T>>::kill(),
+			}
+			<RebalanceThreshold<T>>::put(threshold);
+			<MaxRebalance<T>>::put(max_rebalance);
+			Ok(())
+		}
+
+		/// Sets the tokens minted to `beneficiary` every `era_length` blocks outside the
+		/// curve. `None` stops inflation.
+		pub fn set_inflation(origin, beneficiary: Option<T::AccountId>, #[compact] per_era: u128, era_length: T::BlockNumber) -> Result {
+			Self::_ensure_admin(origin)?;
+			match beneficiary {
+				Some(beneficiary) => <InflationBeneficiary<T>>::put(beneficiary),
+				None => <InflationBeneficiary<T>>::kill(),
+			}
+			<InflationPerEra<T>>::put(per_era);
+			<EraLength<T>>::put(era_length);
+			Ok(())
+		}
+
+		/// Sets or, with `None`, disables the breaker that pauses sells when they take too
+		/// much of the reserve in a window.
+		pub fn set_circuit_breaker(origin, breaker: Option<CircuitBreaker<T::BlockNumber>>) -> Result {
+			Self::_ensure_admin(origin)?;
+			match breaker {
+				Some(breaker) => {
+					ensure!(!breaker.window.is_zero(), "Breaker window must be at least one block.");
+					<SellBreaker<T>>::put(breaker);
+				},
+				None => <SellBreaker<T>>::kill(),
+			}
+			<SellWindow<T>>::put((<system::Module<T>>::block_number(), 0));
+			Ok(())
+		}
+
+		/// Resumes sells paused by the circuit breaker before the cooldown ends. Not
+		/// subject to the update delay.
+		pub fn resume_sells(origin) -> Result {
+			Self::_ensure_governor(origin)?;
+			ensure!(Self::sells_paused(), "Sells are not paused.");
+			let until = Self::sells_paused_until().unwrap_or_else(Zero::zero);
+			<SellsPausedUntil<T>>::kill();
+			<SellWindow<T>>::put((<system::Module<T>>::block_number(), 0));
+			Self::deposit_event(RawEvent::SellsResumed(until));
+			Ok(())
+		}
+
+		/// Resumes trading paused by a failed solvency check. Not subject to the update
+		/// delay. Once governance is renounced, only root can resume.
+		pub fn resume_trading(origin) -> Result {
+			if Self::admin_renounced() {
+				ensure_root(origin)?;
+			} else {
+				Self::_ensure_governor(origin)?;
+			}
+			let paused_at = Self::paused_at().ok_or("Trading is not paused.")?;
+			<Paused<T>>::kill();
+			Self::deposit_event(RawEvent::TradingResumed(paused_at));
+			Ok(())
+		}
+
+		/// Spends up to `amount` of the funding pool buying tokens on the curve, tribute
+		/// included, and burns them. The buy goes through the same checks and records as
+		/// any other, made by the default account, whose key nobody holds. Burning the
+		/// tokens takes the supply back down while the cost stays in the reserve as
+		/// surplus over what sellers are owed. Not subject to the update delay.
+		pub fn buyback(origin, #[compact] amount: u128) -> Result {
+			Self::_ensure_governor(origin)?;
+			ensure!(Self::is_init(), "Token is not initialized.");
+
+			let burner = T::AccountId::default();
+			let tokens = Self::_tokens_for_spend(amount);
+			let (cost, fee) = Self::_check_buy(&burner, tokens, Some(amount))?;
+			let total_ = Self::_to_balance(cost + fee)?;
+			ensure!(total_ <= Self::funding_pool(), "Funding pool cannot cover the buyback.");
+
+			<FundingPool<T>>::mutate(|pool| *pool -= total_);
+			Self::_fill_buy(&burner, burner.clone(), tokens, cost, fee, None)?;
+			Self::_burn(burner.clone(), tokens)?;
+			// The buy must not count towards the default account's purchase cap or locks.
+			<Accounts<T>>::remove(&burner);
+
+			Self::deposit_event(RawEvent::Buyback(tokens, cost));
+			Self::_check_invariants(&[]);
+			Ok(())
+		}
+
+		/// Sets or, with `None`, disables the controller that tunes the slope every era.
+		/// The controller starts from a clean state either way.
+		pub fn set_slope_controller(origin, controller: Option<SlopeController>) -> Result {
+			Self::_ensure_admin(origin)?;
+			if let Some(ref controller) = controller {
+				ensure!((controller.max_step as u128) < BPS, "Slope step must be below 100%.");
+				if let ControlTarget::Volume(target) = controller.target {
+					ensure!(target > 0, "Volume target must be positive.");
+				}
+			}
+
+			match controller {
+				Some(controller) => <SlopeControl<T>>::put(controller),
+				None => <SlopeControl<T>>::kill(),
+			}
+			let volume = Self::cumulative_buy_volume().saturating_add(Self::cumulative_sell_volume());
+			<SlopeControlState<T>>::put(ControllerState { last_volume: volume, ..Default::default() });
+			Ok(())
+		}
+
+		/// Opens a Dutch auction of `tranche` tokens before the curve opens. The price
+		/// falls linearly from `start_price` to `floor_price` over `duration` blocks.
+		pub fn start_auction(origin, #[compact] tranche: u128, #[compact] start_price: u128, #[compact] floor_price: u128, duration: T::BlockNumber) -> Result {
+			Self::_ensure_admin(origin)?;
+			ensure!(Self::is_init(), "Token is not initialized.");
+			ensure!(Self::total_supply() == 0 && Self::auction().is_none(), "Tokens have already been issued.");
+			ensure!(tranche > 0, "Tranche must be positive.");
+			ensure!(floor_price > 0 && floor_price <= start_price, "Floor price must be positive and at most the start price.");
+			ensure!(!duration.is_zero(), "Auction must last at least a block.");
+			ensure!(start_price.checked_mul(tranche).is_some(), "Overflow in auction proceeds.");
+			ensure!(Self::_within_max_supply(tranche), "Tranche exceeds the maximum supply.");
+
+			let start = <system::Module<T>>::block_number();
+			<Auction<T>>::put(DutchAuction {
+				tranche,
+				start_price,
+				floor_price,
+				start,
+				end: start + duration,
+				committed: 0,
+				clearing_price: None,
+			});
+
+			Self::deposit_event(RawEvent::AuctionStarted(tranche, start_price, floor_price));
+			Ok(())
+		}
+
+		/// Bids `amount` currency in the auction. A bid that sells out the tranche at the
+		/// current price closes the auction and only the part needed is taken.
+		pub fn bid(origin, #[compact] amount: u128) -> Result {
+			let sender = ensure_signed(origin)?;
+			let mut auction = Self::auction().ok_or("No auction is running.")?;
+			ensure!(auction.clearing_price.is_none(), "The auction has closed.");
+			ensure!(amount > 0, "Bid must be positive.");
+
+			let now = <system::Module<T>>::block_number();
+			let price = auction.price_at(now);
+			let accepted = cmp::min(amount, (price * auction.tranche).saturating_sub(auction.committed));
+			Self::_withdraw(&sender, Self::_to_balance(accepted)?)?;
+
+			auction.committed += accepted;
+			<AuctionBids<T>>::mutate(&sender, |bid| *bid += accepted);
+			Self::deposit_event(RawEvent::AuctionBid(sender, accepted));
+
+			if auction.committed >= price * auction.tranche {
+				Self::_close_auction(auction, now);
+			} else {
+				<Auction<T>>::put(auction);
+			}
+			Self::_check_invariants(&[]);
+			Ok(())
+		}
+
+		/// Claims the sender's pro-rata share of the tokens sold and of the currency
+		/// committed beyond what they sold for at the clearing price.
+		pub fn claim_auction(origin) -> Result {
+			let sender = ensure_signed(origin)?;
+			let auction = Self::auction().ok_or("No auction is running.")?;
+			let price = auction.clearing_price.ok_or("The auction has not closed.")?;
+			ensure!(Self::auction_bid(&sender) > 0, "No bid to claim.");
+
+			let (tokens, refund) = auction.claim_for(Self::auction_bid(&sender), price);
+			let refund_ = Self::_to_balance(refund)?;
+			ensure!(Self::reserve() >= refund_, "Liquid reserve cannot cover the refund.");
+
+			<AuctionBids<T>>::remove(&sender);
+			<AuctionUnclaimed<T>>::mutate(|unclaimed| *unclaimed -= tokens);
+			Self::_set_balance(&sender, Self::balance_of(&sender) + tokens);
+			if !refund_.is_zero() {
+				<Reserve<T>>::mutate(|reserve| *reserve -= refund_);
+				<balances::Module<T>>::increase_free_balance_creating(&sender, refund_);
+			}
+
+			Self::deposit_event(RawEvent::Transfer(None, Some(sender), tokens));
+			Self::_check_invariants(&[]);
+			Ok(())
+		}
+
+		/// Starts a launch phase in which the slope decays from `start_slope` to the
+		/// configured slope over `duration` blocks, making early buys expensive.
+		/// The reserve must cover what sellers of the existing supply are owed on the
+		/// launch curve.
+		pub fn start_launch(origin, start_slope: u128, duration: T::BlockNumber) -> Result {
+			Self::_ensure_admin(origin)?;
+			ensure!(!duration.is_zero(), "Launch must last at least a block.");
+			ensure!(start_slope >= Self::slope(), "Launch slope is below the configured slope.");
+			let params = CurveParams { slope: start_slope, ..Self::curve_params() };
+			params.validate(Self::max_supply())?;
+			let covered = params.area(0, Self::_backed_supply(), true)
+				.map_or(false, |owed| owed <= Self::_total_reserve());
+			ensure!(covered, "Reserve cannot cover the supply on the launch curve.");
+
+			<LaunchSlope<T>>::put(start_slope);
+			<LaunchDuration<T>>::put(duration);
+			<LaunchEnd<T>>::put(<system::Module<T>>::block_number() + duration);
+			Ok(())
+		}
+
+		/// Schedules changes of the curve's slope and exponent at the given blocks,
+		/// replacing any pending schedule. Steps must be in block order.
+		pub fn set_curve_schedule(origin, steps: Vec<(T::BlockNumber, u128, u128)>) -> Result {
+			Self::_ensure_admin(origin)?;
+			ensure!(steps.len() <= MAX_CURVE_STEPS, "Too many curve steps.");
+			ensure!(steps.windows(2).all(|w| w[0].0 < w[1].0), "Curve steps must be in block order.");
+			for (_, slope, exponent) in &steps {
+				let params = CurveParams { slope: *slope, exponent: *exponent, ..Self::curve_params() };
+				params.validate(Self::max_supply())?;
+			}
+
+			<CurveSchedule<T>>::put(steps);
+			Ok(())
+		}
+
+		/// Sets how many blocks old an oracle price may be before it is ignored, zero
+		/// for no limit.
+		pub fn set_oracle_max_age(origin, max_age: T::BlockNumber) -> Result {
+			Self::_ensure_admin(origin)?;
+			<OracleMaxAge<T>>::put(max_age);
+			Ok(())
+		}
+
+		/// Burns `tokens` for their pro-rata share of the whole reserve instead of the
+		/// curve return. Only available once governance enables it.
+		pub fn redeem_underlying(origin, #[compact] tokens: u128) -> Result {
+			let sender = ensure_signed(origin)?;
+			ensure!(Self::redemption_enabled(), "Pro-rata redemption is not enabled.");
+			ensure!(!Self::paused(), "Trading is paused.");
+			ensure!(tokens >= T::MIN_TRADE, "Redemption is below the minimum trade size.");
+			ensure!(Self::balance_of(&sender) >= tokens, "Not enough balance.");
+			ensure!(Self::sellable_balance(&sender) >= tokens, "Tokens are still locked after purchase.");
+
+			let share = Self::_total_reserve().checked_mul(tokens).ok_or("Overflow in calculating redemption.")? / Self::total_supply();
+			let share_ = Self::_to_balance(share)?;
+			ensure!(Self::reserve() >= share_, "Liquid reserve cannot cover the redemption.");
+			ensure!(Self::_can_receive(&sender, share_), "Payout is below the existential deposit.");
+
+			Self::_with_spend_limit(&sender, tokens, || Self::_burn(sender.clone(), tokens))?;
+			<Reserve<T>>::mutate(|reserve| *reserve -= share_);
+			<balances::Module<T>>::increase_free_balance_creating(&sender, share_);
+
+			Self::deposit_event(RawEvent::Redeemed(sender, tokens, share_));
+			Self::_check_invariants(&[]);
+			Ok(())
+		}
+
+		/// Enables or disables pro-rata redemption of the reserve.
+		pub fn set_redemption_enabled(origin, enabled: bool) -> Result {
+			Self::_ensure_admin(origin)?;
+			<RedemptionEnabled<T>>::put(enabled);
+			Ok(())
+		}
+
+		/// Queues `call` to be applied as a parameter update once the update delay has
+		/// passed, giving holders time to exit before it takes effect. The call is
+		/// dispatched with the origin that queued it, so the admin cannot queue calls
+		/// only root may make.
+		pub fn queue_update(origin, call: Box<Call<T>>) -> Result {
+			let queuer = Self::_governor(origin)?;
+			let id = Self::next_update_id();
+			let next_id = id.checked_add(1).ok_or("Overflow in update identifier.")?;
+			// An update never applies in the block it was queued in.
+			let due = <system::Module<T>>::block_number() + cmp::max(Self::update_delay(), As::sa(1));
+
+			<NextUpdateId<T>>::put(next_id);
+			<UpdateTallyMode<T>>::insert(id, Self::tally_mode(Self::update_category(&call)));
+			<PendingUpdates<T>>::insert(id, (due, *call, queuer));
+			<UpdatesDue<T>>::mutate(due, |ids| ids.push(id));
+
+			Self::deposit_event(RawEvent::UpdateQueued(id, due));
+			Ok(())
+		}
+
+		/// Drops a queued parameter update before it applies.
+		pub fn cancel_pending_update(origin, id: u64) -> Result {
+			Self::_ensure_governor(origin)?;
+			ensure!(<PendingUpdates<T>>::exists(id), "Update is not pending.");
+			<PendingUpdates<T>>::remove(id);
+			<VetoTally<T>>::remove(id);
+			<UpdateTallyMode<T>>::remove(id);
+			Self::deposit_event(RawEvent::UpdateCancelled(id));
+			Ok(())
+		}
+
+		/// Locks `tokens` behind a veto of a queued update. The update is cancelled when
+		/// it falls due if the votes vetoing it exceed the veto threshold of the supply.
+		/// Votes are multiplied by the conviction, for which the tokens stay locked for as
+		/// many vote lock eras after the update falls due.
+		pub fn veto(origin, id: u64, #[compact] tokens: u128, conviction: Conviction) -> Result {
+			let sender = ensure_signed(origin)?;
+			ensure!(Self::veto_threshold() > 0, "Vetoes are not enabled.");
+			let (due, _, _) = Self::pending_update(id).ok_or("Update is not pending.")?;
+			ensure!(tokens > 0, "Veto must lock some tokens.");
+			// The voter's own tokens are used first, then power delegated to it.
+			let free = Self::balance_of(&sender).saturating_sub(Self::_locked(&sender));
+			let free_power = Self::delegated_power(&sender).saturating_sub(Self::delegated_power_locked(&sender));
+			ensure!(free.saturating_add(free_power) >= tokens, "Not enough unlocked balance or delegated power.");
+			let own = cmp::min(free, tokens);
+			let delegated = tokens - own;
+
+			let mode = Self::update_tally_mode(id);
+			let lock = Self::vote_lock_era() * As::sa(conviction.multiplier() as u64);
+			let previous = match Self::veto_of((id, sender.clone())) {
+				Some(vote) => {
+					ensure!(vote.conviction == conviction, "Veto was cast with a different conviction.");
+					vote
+				},
+				None => Vote::default(),
+			};
+			let vetoed = previous.tokens;
+			let added = (mode.weight(vetoed + tokens) - mode.weight(vetoed)) * conviction.multiplier();
+			<Vetoes<T>>::insert((id, sender.clone()), Vote {
+				tokens: vetoed + tokens,
+				delegated: previous.delegated + delegated,
+				conviction,
+				unlock: due + lock,
+			});
+			<VetoTally<T>>::mutate(id, |tally| *tally += added);
+			Self::_mutate_account(&sender, |account| account.veto_locked += own);
+			<DelegatedPowerLocked<T>>::mutate(&sender, |locked| *locked += delegated);
+
+			Self::deposit_event(RawEvent::Vetoed(id, sender, tokens));
+			Ok(())
+		}
+
+		/// Unlocks the tokens behind a veto. Withdrawing before the update falls due
+		/// takes them off its tally; afterwards the conviction lock must have passed.
+		pub fn withdraw_veto(origin, id: u64) -> Result {
+			let sender = ensure_signed(origin)?;
+			let vote = Self::veto_of((id, sender.clone())).ok_or("No veto to withdraw.")?;
+
+			if <PendingUpdates<T>>::exists(id) {
+				let votes = Self::update_tally_mode(id).weight(vote.tokens) * vote.conviction.multiplier();
+				<VetoTally<T>>::mutate(id, |tally| *tally = tally.saturating_sub(votes));
+			} else {
+				let now = <system::Module<T>>::block_number();
+				ensure!(now >= vote.unlock, "Veto is still locked by its conviction.");
+			}
+			<Vetoes<T>>::remove((id, sender.clone()));
+			Self::_mutate_account(&sender, |account| {
+				account.veto_locked = account.veto_locked.saturating_sub(vote.tokens - vote.delegated);
+			});
+			<DelegatedPowerLocked<T>>::mutate(&sender, |locked| *locked = locked.saturating_sub(vote.delegated));
+			Ok(())
+		}
+
+		/// Locks `tokens` and delegates their voting power to `to`, replacing any earlier
+		/// delegation. Delegated power cannot be delegated on, so it counts only once.
+		pub fn delegate(origin, to: T::AccountId, #[compact] tokens: u128) -> Result {
+			let sender = ensure_signed(origin)?;
+			ensure!(sender != to, "Cannot delegate to yourself.");
+			ensure!(tokens > 0, "Delegation must lock some tokens.");
+			let previous = Self::delegation(&sender);
+			if let Some((ref delegate, delegated)) = previous {
+				Self::_ensure_undelegatable(delegate, delegated)?;
+			}
+			let released = previous.as_ref().map_or(0, |(_, delegated)| *delegated);
+			let free = Self::balance_of(&sender).saturating_sub(Self::_locked(&sender)) + released;
+			ensure!(free >= tokens, "Not enough unlocked balance.");
+
+			if let Some((delegate, delegated)) = previous {
+				<DelegatedPower<T>>::mutate(&delegate, |power| *power -= delegated);
+				Self::deposit_event(RawEvent::Undelegated(sender.clone(), delegate, delegated));
+			}
+			Self::_mutate_account(&sender, |account| account.delegation = Some((to.clone(), tokens)));
+			<DelegatedPower<T>>::mutate(&to, |power| *power += tokens);
+
+			Self::deposit_event(RawEvent::Delegated(sender, to, tokens));
+			Ok(())
+		}
+
+		/// Withdraws a delegation and unlocks its tokens, unless the delegate has the
+		/// power locked behind vetoes.
+		pub fn undelegate(origin) -> Result {
+			let sender = ensure_signed(origin)?;
+			let (delegate, delegated) = Self::delegation(&sender).ok_or("No delegation to withdraw.")?;
+			Self::_ensure_undelegatable(&delegate, delegated)?;
+
+			Self::_mutate_account(&sender, |account| account.delegation = None);
+			<DelegatedPower<T>>::mutate(&delegate, |power| *power -= delegated);
+
+			Self::deposit_event(RawEvent::Undelegated(sender, delegate, delegated));
+			Ok(())
+		}
+
+		/// Sets the blocks in one vote lock era.
+		pub fn set_vote_lock_era(origin, era: T::BlockNumber) -> Result {
+			Self::_ensure_admin(origin)?;
+			<VoteLockEra<T>>::put(era);
+			Ok(())
+		}
+
+		/// Sets the share of the supply whose vetoes cancel a queued update, in basis
+		/// points, zero to disable vetoes.
+		pub fn set_veto_threshold(origin, threshold: u32) -> Result {
+			Self::_ensure_admin(origin)?;
+			ensure!(threshold as u128 <= BPS, "Veto threshold cannot exceed 100%.");
+			<VetoThreshold<T>>::put(threshold);
+			Ok(())
+		}
+
+		/// Sets how vetoes of updates in `category` are tallied. Updates already queued
+		/// keep the mode they were queued with.
+		pub fn set_tally_mode(origin, category: UpdateCategory, mode: TallyMode) -> Result {
+			Self::_ensure_admin(origin)?;
+			<TallyModes<T>>::insert(category, mode);
+			Ok(())
+		}
+
+		/// Sets how many blocks parameter updates wait in the queue.
+		pub fn set_update_delay(origin, delay: T::BlockNumber) -> Result {
+			Self::_ensure_admin(origin)?;
+			<UpdateDelay<T>>::put(delay);
+			Ok(())
+		}
+
+		/// Hands governance of the token to `new`, e.g. a multisig or council account.
+		pub fn transfer_admin(origin, new: T::AccountId) -> Result {
+			Self::_ensure_admin(origin)?;
+			let old = Self::admin();
+			<Admin<T>>::put(new.clone());
+			Self::deposit_event(RawEvent::AdminTransferred(old, new));
+			Ok(())
+		}
+
+		/// Gives up governance of the token for good. Neither the admin nor root can
+		/// change its parameters afterwards.
+		pub fn renounce_admin(origin) -> Result {
+			Self::_ensure_admin(origin)?;
+			let old = Self::admin();
+			<Admin<T>>::kill();
+			<AdminRenounced<T>>::put(true);
+			Self::deposit_event(RawEvent::AdminRenounced(old));
+			Ok(())
+		}
+
+		/// Resets the total supply to the tokens actually held, repairing any drift
+		/// reported by `audit_supply`.
+		pub fn force_reconcile(origin) -> Result {
+			ensure_root(origin)?;
+
+			let old = Self::total_supply();
+			let new = Self::_held_supply();
+			ensure!(old != new, "Total supply already matches the tokens held.");
+			Self::_set_supply(new);
+
+			Self::deposit_event(RawEvent::SupplyReconciled(old, new));
+			Self::_check_invariants(&[]);
+			Ok(())
+		}
+
+		/// Recomputes the reserve from what sellers of the backed supply are owed and
+		/// moves the difference between the liquid reserve and `account`. Surplus is
+		/// paid to `account`; a shortfall is taken from it. For recovering from
+		/// accounting drift only.
+		pub fn reconcile_reserve(origin, account: T::AccountId) -> Result {
+			ensure_root(origin)?;
+
+			let old = Self::_total_reserve();
+			let owed = Self::curve_params().area(0, Self::_backed_supply(), false).ok_or("Curve obligation overflows.")?;
+			ensure!(old != owed, "Reserve already matches the curve obligation.");
+
+			if old > owed {
+				let surplus = cmp::min(Self::_to_balance(old - owed)?, Self::reserve());
+				ensure!(!surplus.is_zero(), "Surplus is deployed and cannot be moved.");
+				<Reserve<T>>::mutate(|reserve| *reserve -= surplus);
+				<balances::Module<T>>::increase_free_balance_creating(&account, surplus);
+			} else {
+				let shortfall = Self::_to_balance(owed - old)?;
+				Self::_withdraw(&account, shortfall)?;
+				<Reserve<T>>::mutate(|reserve| *reserve += shortfall);
+			}
+
+			Self::deposit_event(RawEvent::ReserveReconciled(account, old, Self::_total_reserve()));
+			Self::_check_invariants(&[]);
+			Ok(())
+		}
+
+		/// Removes an explicitly stored zero balance. Anyone may call this.
+		pub fn reap(origin, who: T::AccountId) -> Result {
+			ensure_signed(origin)?;
+			ensure!(Self::_has_balance_entry(&who), "Account has no balance entry.");
+			ensure!(Self::balance_of(&who) == 0, "Account still holds tokens.");
+
+			<Balances<T>>::remove(T::Hashing::hash_of(&who));
+			<BalanceOf<T>>::remove(&who);
+			Ok(())
+		}
+
+		/// Moves the legacy balances of `accounts` under their hashed keys, counting them
+		/// as holders. Anyone may call this.
+		pub fn migrate_balances(origin, accounts: Vec<T::AccountId>) -> Result {
+			ensure_signed(origin)?;
+			ensure!(accounts.len() <= MAX_MIGRATION_BATCH, "Too many accounts to migrate at once.");
+
+			for who in accounts {
+				let legacy = Self::legacy_balance_of(&who);
+				if legacy > 0 {
+					Self::_set_balance(&who, legacy);
+				}
+			}
+			Self::_check_invariants(&[]);
+			Ok(())
+		}
+
+		/// Removes the sender's zero-value allowances and returns their deposits.
+		pub fn clear_allowances(origin) -> Result {
+			let sender = ensure_signed(origin)?;
+
+			let (cleared, kept): (Vec<_>, Vec<_>) = Self::spenders(&sender).into_iter()
+				.partition(|(spender, _)| Self::allowance((sender.clone(), spender.clone())) == 0);
+			for (spender, deposit) in cleared {
+				Self::_set_allowance((sender.clone(), spender), None);
+				<balances::Module<T>>::unreserve(&sender, deposit);
+			}
+			<Spenders<T>>::insert(&sender, kept);
+
+			Self::_check_invariants(&[&sender]);
+			Ok(())
+		}
+
+		/// Revokes every allowance of the sender and returns their deposits. The owner's
+		/// spender index bounds the work to the maximum number of allowances.
+		pub fn revoke_all_allowances(origin) -> Result {
+			let sender = ensure_signed(origin)?;
+			let spenders = <Spenders<T>>::take(&sender);
+			ensure!(!spenders.is_empty(), "No allowances to revoke.");
+
+			let revoked = spenders.len() as u32;
+			for (spender, deposit) in spenders {
+				Self::_set_allowance((sender.clone(), spender), None);
+				<balances::Module<T>>::unreserve(&sender, deposit);
+			}
+
+			Self::deposit_event(RawEvent::AllowancesRevoked(sender.clone(), revoked));
+			Self::_check_invariants(&[&sender]);
+			Ok(())
+		}
+
+		/// Sets the deposit reserved for each new allowance.
+		pub fn set_allowance_deposit(origin, #[compact] deposit: T::Balance) -> Result {
+			Self::_ensure_admin(origin)?;
+			<AllowanceDeposit<T>>::put(deposit);
+			Ok(())
+		}
+
+		/// Queues a buy of `tokens` paying at most `max_price` per token.
+		///
+		/// The worst-case cost is held until the end of the block, where the order is
+		/// matched against queued sells before any residual is bought from the curve.
+		pub fn queue_buy(origin, #[compact] tokens: u128, #[compact] max_price: u128) -> Result {
+			let sender = ensure_signed(origin)?;
+			ensure!(tokens > 0 && tokens >= T::MIN_TRADE, "Order is below the minimum trade size.");
+
+			ensure!(Self::buy_queue().len() < MAX_ORDERS, "Buy queue is full.");
+
+			let escrow = tokens.checked_mul(max_price).ok_or("Overflow in calculating escrow.")?;
+			let escrow_ = Self::_to_balance(escrow)?;
+			let fee = Self::order_fee();
+			let total = escrow_.checked_add(&fee).ok_or("Overflow in calculating escrow.")?;
+
+			Self::_withdraw(&sender, total)?;
+			<KeeperPot<T>>::mutate(|pot| *pot += fee);
+			<BuyQueue<T>>::mutate(|queue| queue.push(Order { who: sender.clone(), tokens, limit: max_price, escrow, resting: false }));
+
+			Self::deposit_event(RawEvent::BuyQueued(sender, tokens, max_price));
+			Self::_check_invariants(&[]);
+			Ok(())
+		}
+
+		/// Queues a sell of `tokens` accepting no less than `min_price` per token.
+		///
+		/// The tokens are held by the order until it is settled at the end of the block.
+		pub fn queue_sell(origin, #[compact] tokens: u128, #[compact] min_price: u128) -> Result {
+			let sender = ensure_signed(origin)?;
+			ensure!(tokens > 0 && tokens >= T::MIN_TRADE, "Order is below the minimum trade size.");
+			ensure!(Self::_cooled_down(&sender), "Account bought too recently to sell.");
+			ensure!(Self::sellable_balance(&sender) >= tokens, "Tokens are still locked after purchase.");
+			ensure!(Self::sell_queue().len() < MAX_ORDERS, "Sell queue is full.");
+
+			let balance = Self::balance_of(&sender);
+			let new_balance = balance.checked_sub(tokens).ok_or("Not enough balance.")?;
+			let fee = Self::order_fee();
+
+			// The queued tokens count against the spend limit once the fee is paid.
+			Self::_with_spend_limit(&sender, tokens, || Self::_withdraw(&sender, fee))?;
+			<KeeperPot<T>>::mutate(|pot| *pot += fee);
+			Self::_set_balance(&sender, new_balance);
+			<SellQueue<T>>::mutate(|queue| queue.push(Order { who: sender.clone(), tokens, limit: min_price, escrow: 0, resting: false }));
+
+			Self::deposit_event(RawEvent::SellQueued(sender, tokens, min_price));
+			Self::_check_invariants(&[]);
+			Ok(())
+		}
+
+		/// Settles every order whose limit can currently be met and pays the caller the
+		/// keeper bounty out of the keeper pot.
+		pub fn execute_orders(origin) -> Result {
+			let sender = ensure_signed(origin)?;
+
+			let filled = Self::_settle_orders();
+			ensure!(filled > 0, "No orders were due.");
+
+			let bounty = cmp::min(Self::keeper_bounty(), Self::keeper_pot());
+			if !bounty.is_zero() {
+				<KeeperPot<T>>::mutate(|pot| *pot -= bounty);
+				<balances::Module<T>>::increase_free_balance_creating(&sender, bounty);
+			}
+
+			Self::deposit_event(RawEvent::OrdersExecuted(sender, filled, bounty));
+			Self::_check_invariants(&[]);
+			Ok(())
+		}
+
+		/// Cancels all of the sender's resting orders, returning held tokens and escrow.
+		pub fn cancel_orders(origin) -> Result {
+			let sender = ensure_signed(origin)?;
+
+			let (mine, rest): (Vec<_>, Vec<_>) = Self::buy_queue().into_iter().partition(|o| o.who == sender);
+			<BuyQueue<T>>::put(rest);
+			for order in mine {
+				<balances::Module<T>>::increase_free_balance_creating(&order.who, Self::_saturating_balance(order.escrow));
+				Self::deposit_event(RawEvent::OrderRefunded(order.who, order.tokens));
+			}
+
+			let (mine, rest): (Vec<_>, Vec<_>) = Self::sell_queue().into_iter().partition(|o| o.who == sender);
+			<SellQueue<T>>::put(rest);
+			for order in mine {
+				Self::_set_balance(&order.who, Self::balance_of(&order.who) + order.tokens);
+				Self::deposit_event(RawEvent::OrderRefunded(order.who, order.tokens));
+			}
+
+			Self::_check_invariants(&[]);
+			Ok(())
+		}
+
+		/// Sets the order placement fee and the keeper bounty.
+		pub fn set_keeper_incentive(origin, #[compact] order_fee: T::Balance, #[compact] bounty: T::Balance) -> Result {
+			Self::_ensure_admin(origin)?;
+
+			<OrderFee<T>>::put(order_fee);
+			<KeeperBounty<T>>::put(bounty);
+
+			Ok(())
+		}
+
+		/// Schedules `total_periods` purchases of `amount_per_interval` currency worth of
+		/// tokens, one every `interval` blocks. The full amount is held upfront.
+		pub fn schedule_recurring_buy(origin, #[compact] amount_per_interval: u128, interval: T::BlockNumber, total_periods: u32) -> Result {
+			let sender = ensure_signed(origin)?;
+			ensure!(amount_per_interval > 0, "Recurring buy amount must be positive.");
+			ensure!(!interval.is_zero(), "Recurring buy interval must be positive.");
+			ensure!(total_periods > 0, "Recurring buy needs at least one period.");
+
+			let escrow = amount_per_interval.checked_mul(total_periods as u128).ok_or("Overflow in calculating escrow.")?;
+			let id = Self::next_recurring_buy_id();
+			let next_id = id.checked_add(1).ok_or("Overflow in recurring buy identifier.")?;
+			let due = Self::_recurring_slot(<system::Module<T>>::block_number() + interval)
+				.ok_or("Too many recurring buys are due around that block.")?;
+
+			Self::_withdraw(&sender, Self::_to_balance(escrow)?)?;
+
+			<NextRecurringBuyId<T>>::put(next_id);
+			<RecurringBuys<T>>::insert(id, RecurringBuy {
+				who: sender.clone(),
+				amount: amount_per_interval,
+				interval,
+				remaining: total_periods,
+				escrow,
+			});
+			<RecurringBuysDue<T>>::mutate(due, |ids| ids.push(id));
+
+			Self::deposit_event(RawEvent::RecurringBuyScheduled(id, sender, amount_per_interval, total_periods));
+			Self::_check_invariants(&[]);
+			Ok(())
+		}
+
+		/// Cancels a recurring buy and refunds the currency not yet spent.
+		pub fn cancel_recurring_buy(origin, id: u64) -> Result {
+			let sender = ensure_signed(origin)?;
+			let plan = Self::recurring_buy(id).ok_or("Recurring buy does not exist.")?;
+			ensure!(plan.who == sender, "Not the owner of this recurring buy.");
+
+			<RecurringBuys<T>>::remove(id);
+			<balances::Module<T>>::increase_free_balance_creating(&sender, Self::_saturating_balance(plan.escrow));
+
+			Self::deposit_event(RawEvent::RecurringBuyCancelled(id, sender, plan.escrow));
+			Self::_check_invariants(&[]);
+			Ok(())
+		}
+
+		/// Sets the rebate paid to resting orders matched internally, in basis points.
+		pub fn set_maker_rebate_rate(origin, rate: u32) -> Result {
+			Self::_ensure_admin(origin)?;
+			ensure!(rate as u128 <= BPS, "Maker rebate cannot exceed 100%.");
+			<MakerRebateRate<T>>::put(rate);
+			Ok(())
+		}
+
+		/// Sets the most tokens a single flash mint may create, zero to disable, and the
+		/// flash mint fee in basis points.
+		pub fn set_flash_mint_config(origin, #[compact] limit: u128, fee: u32) -> Result {
+			Self::_ensure_admin(origin)?;
+			ensure!(fee as u128 <= BPS, "Flash mint fee cannot exceed 100%.");
+			<FlashMintLimit<T>>::put(limit);
+			<FlashMintFee<T>>::put(fee);
+			Ok(())
+		}
+
+		/// Pays the maker rebates earned by the sender.
+		pub fn claim_maker_rebates(origin) -> Result {
+			let sender = ensure_signed(origin)?;
+			let earned = Self::maker_rebates(&sender);
+			ensure!(earned > 0, "No maker rebates to claim.");
+			let earned_ = Self::_to_balance(earned)?;
+			ensure!(Self::_can_receive(&sender, earned_), "Rebate is below the existential deposit.");
+
+			<MakerRebates<T>>::remove(&sender);
+			<balances::Module<T>>::increase_free_balance_creating(&sender, earned_);
+
+			Self::deposit_event(RawEvent::MakerRebatesClaimed(sender, earned));
+			Ok(())
+		}
+
+		/// Pays the tribute shares allotted to the sender as a beneficiary.
+		pub fn claim_beneficiary_share(origin) -> Result {
+			let sender = ensure_signed(origin)?;
+			let owed = Self::beneficiary_shares(&sender);
+			ensure!(owed > 0, "No beneficiary share to claim.");
+			let owed_ = Self::_to_balance(owed)?;
+			ensure!(Self::_can_receive(&sender, owed_), "Share is below the existential deposit.");
+
+			<BeneficiaryShares<T>>::remove(&sender);
+			<balances::Module<T>>::increase_free_balance_creating(&sender, owed_);
+
+			Self::deposit_event(RawEvent::BeneficiaryShareClaimed(sender, owed));
+			Ok(())
+		}
+
+		/// Pays the referral rewards earned by the sender.
+		pub fn claim_referral_rewards(origin) -> Result {
+			let sender = ensure_signed(origin)?;
+			let owed = Self::referral_rewards(&sender);
+			ensure!(owed > 0, "No referral rewards to claim.");
+			let owed_ = Self::_to_balance(owed)?;
+			ensure!(Self::_can_receive(&sender, owed_), "Reward is below the existential deposit.");
+
+			<ReferralRewards<T>>::remove(&sender);
+			<balances::Module<T>>::increase_free_balance_creating(&sender, owed_);
+
+			Self::deposit_event(RawEvent::ReferralRewardsClaimed(sender, owed));
+			Ok(())
+		}
+
+		/// Sets or, with `None`, clears the URI of the token's metadata.
+		pub fn set_metadata_uri(origin, uri: Option<Vec<u8>>) -> Result {
+			Self::_ensure_admin(origin)?;
+			if let Some(ref uri) = uri {
+				ensure!(!uri.is_empty(), "Metadata URI cannot be empty.");
+				ensure!(uri.len() <= MAX_METADATA_URI_LEN, "Metadata URI is too long.");
+			}
+
+			match uri.clone() {
+				Some(uri) => <MetadataUri<T>>::put(uri),
+				None => <MetadataUri<T>>::kill(),
+			}
+
+			Self::deposit_event(RawEvent::MetadataUriChanged(uri));
+			Ok(())
+		}
+
+		/// Sets how often curve statistics are published, zero to disable.
+		pub fn set_stats_period(origin, period: T::BlockNumber) -> Result {
+			Self::_ensure_admin(origin)?;
+			<StatsPeriod<T>>::put(period);
+			Ok(())
+		}
+
+		/// Sets the blocks the time-weighted average price is taken over, zero to
+		/// disable. The next average is ready once a full window has passed.
+		pub fn set_twap_window(origin, window: T::BlockNumber) -> Result {
+			Self::_ensure_admin(origin)?;
+			<TwapWindow<T>>::put(window);
+			<TwapStart<T>>::put((<system::Module<T>>::block_number(), Self::price_cumulative()));
+			Ok(())
+		}
+
+		/// Sets how often a solvency report is emitted, zero to disable.
+		pub fn set_solvency_report_period(origin, period: T::BlockNumber) -> Result {
+			Self::_ensure_admin(origin)?;
+			<SolvencyReportPeriod<T>>::put(period);
+			Ok(())
+		}
+
+		/// Sets how many recent trades are kept, clearing the current history.
+		pub fn set_trade_history_size(origin, size: u32) -> Result {
+			Self::_ensure_admin(origin)?;
+
+			for slot in 0..Self::trade_history_size() {
+				<TradeHistory<T>>::remove(slot);
+			}
+			<TradeHistoryCursor<T>>::put(0);
+			<PruneCursor<T>>::put(0);
+			<TradeHistorySize<T>>::put(size);
+
+			Ok(())
+		}
+
+		/// Sets the blocks in a candle window and how many windows are kept, clearing the
+		/// current candles.
+		pub fn set_candle_config(origin, length: T::BlockNumber, retention: u64) -> Result {
+			Self::_ensure_admin(origin)?;
+			ensure!(length.is_zero() || retention > 0, "Candles must be kept for at least one window.");
+			ensure!(retention <= MAX_CANDLE_RETENTION, "Candle retention is above the maximum.");
+
+			let latest = Self::latest_candle();
+			for window in latest.saturating_sub(Self::candle_retention())..=latest {
+				<Candles<T>>::remove(window);
+			}
+			<LatestCandle<T>>::kill();
+			<CandleLength<T>>::put(length);
+			<CandleRetention<T>>::put(retention);
+
+			Ok(())
+		}
+
+		/// Sets the lowest reserve ratio a sell may leave, in basis points, zero to disable.
+		pub fn set_min_reserve_ratio(origin, ratio: u32) -> Result {
+			Self::_ensure_admin(origin)?;
+			<MinReserveRatio<T>>::put(ratio);
+			Ok(())
+		}
+
+		/// Sets the reserve ratios, in basis points, below which a trade emits
+		/// `ReserveRatioLow`. An empty list disables the alerts.
+		pub fn set_ratio_alerts(origin, thresholds: Vec<u32>) -> Result {
+			Self::_ensure_admin(origin)?;
+			ensure!(thresholds.len() <= MAX_RATIO_ALERTS, "Too many reserve ratio alerts.");
+			<RatioAlerts<T>>::put(thresholds);
+			<LastReserveRatio<T>>::kill();
+			Ok(())
+		}
+
+		/// Sets the age in blocks past which historical data can be pruned, zero to
+		/// disable pruning.
+		pub fn set_history_horizon(origin, horizon: T::BlockNumber) -> Result {
+			Self::_ensure_admin(origin)?;
+			<HistoryHorizon<T>>::put(horizon);
+			Ok(())
+		}
+
+		/// Removes candles and trade history older than the history horizon. Anyone can
+		/// call this; each call looks at a bounded number of trade history slots,
+		/// carrying on from where the last one stopped.
+		pub fn prune(origin) -> Result {
+			let _ = ensure_signed(origin)?;
+			let horizon = Self::history_horizon();
+			ensure!(!horizon.is_zero(), "No history horizon is set.");
+			let now = <system::Module<T>>::block_number();
+			ensure!(now > horizon, "Nothing is older than the history horizon yet.");
+			let cutoff = now - horizon;
+
+			let mut pruned = 0;
+			let size = Self::trade_history_size();
+			if size > 0 {
+				let start = Self::prune_cursor() % size;
+				let slots = cmp::min(size, MAX_PRUNE_SLOTS);
+				for i in 0..slots {
+					let slot = (start + i) % size;
+					if Self::trade_history(slot).map_or(false, |record| record.block < cutoff) {
+						<TradeHistory<T>>::remove(slot);
+						pruned += 1;
+					}
+				}
+				<PruneCursor<T>>::put((start + slots) % size);
+			}
+
+			let length = Self::candle_length();
+			if !length.is_zero() {
+				let window: u64 = (cutoff / length).as_();
+				pruned += Self::_prune_candles(window);
+			}
+
+			Self::deposit_event(RawEvent::HistoryPruned(pruned));
+			Ok(())
+		}
+
+		/// Takes a snapshot of all balances. Balances are checkpointed lazily the next
+		/// time they change, so this is constant cost.
+		pub fn take_snapshot(origin) -> Result {
+			Self::_ensure_admin(origin)?;
+
+			let id = Self::snapshot_id().checked_add(1).ok_or("Overflow in snapshot identifier.")?;
+			<SnapshotId<T>>::put(id);
+
+			Self::deposit_event(RawEvent::Snapshot(id));
+			Ok(())
+		}
+
+		/// Posts a distribution of at most `total` tokens whose allocations are committed
+		/// to by the Merkle `root`.
+		pub fn post_distribution(origin, root: T::Hash, #[compact] total: u128) -> Result {
+			Self::_ensure_admin(origin)?;
+
+			let id = Self::next_distribution_id();
+			let next_id = id.checked_add(1).ok_or("Overflow in distribution identifier.")?;
+
+			<NextDistributionId<T>>::put(next_id);
+			<Distributions<T>>::insert(id, Distribution { root, remaining: total });
+
+			Self::deposit_event(RawEvent::DistributionPosted(id, root, total));
+			Ok(())
+		}
+
+		/// Claims the sender's `amount` from distribution `id`, proven by the sibling
+		/// hashes on the path from its leaf to the root. The claimed tokens are backed
+		/// from the funding pool.
+		pub fn claim(origin, id: u32, #[compact] amount: u128, proof: Vec<T::Hash>) -> Result {
+			let sender = ensure_signed(origin)?;
+
+			let mut distribution = Self::distribution(id).ok_or("Distribution does not exist.")?;
+			ensure!(!Self::claimed((id, sender.clone())), "Already claimed.");
+
+			let leaf = T::Hashing::hash_of(&(sender.clone(), amount));
+			ensure!(Self::_merkle_root(leaf, &proof) == distribution.root, "Invalid Merkle proof.");
+
+			distribution.remaining = distribution.remaining.checked_sub(amount).ok_or("Distribution is exhausted.")?;
+
+			Self::_mint_backed(sender.clone(), amount)?;
+			<Distributions<T>>::insert(id, distribution);
+			<Claimed<T>>::insert((id, sender.clone()), true);
+
+			Self::deposit_event(RawEvent::Claimed(id, sender, amount));
+			Self::_check_invariants(&[]);
+			Ok(())
+		}
+
+		/// Sets the entry tribute and the referrer's share of it, both in basis points.
+		pub fn set_entry_fee(origin, entry_fee: u32, referral_reward: u32) -> Result {
+			Self::_ensure_admin(origin)?;
+			ensure!(entry_fee as u128 <= BPS, "Entry fee cannot exceed 100%.");
+			ensure!(entry_fee <= T::MAX_ENTRY_FEE, "Entry fee is above the runtime maximum.");
+			ensure!(referral_reward as u128 <= BPS, "Referral reward cannot exceed 100%.");
+
+			<EntryFee<T>>::put(entry_fee);
+			<ReferralReward<T>>::put(referral_reward);
+
+			Ok(())
+		}
+
+		/// Sets the royalty on transfers in basis points.
+		pub fn set_transfer_royalty(origin, royalty: u32) -> Result {
+			Self::_ensure_admin(origin)?;
+			ensure!(royalty as u128 <= BPS, "Royalty cannot exceed 100%.");
+			<TransferRoyalty<T>>::put(royalty);
+			Ok(())
+		}
+
+		/// Pays the royalties accrued from transfers to the creator.
+		pub fn claim_royalties(origin) -> Result {
+			let sender = ensure_signed(origin)?;
+			ensure!(Self::creator() == Some(sender.clone()), "Only the creator can claim royalties.");
+
+			let accrued = <RoyaltiesAccrued<T>>::take();
+			Self::_set_balance(&sender, Self::balance_of(&sender).checked_add(accrued).ok_or("Overflow in calculating balance.")?);
+
+			Self::deposit_event(RawEvent::RoyaltiesClaimed(sender, accrued));
+			Self::_check_invariants(&[]);
+			Ok(())
+		}
+
+		/// Pays the creator its share of the tributes collected since the last claim.
+		pub fn claim_creator_fees(origin) -> Result {
+			let sender = ensure_signed(origin)?;
+			ensure!(Self::creator() == Some(sender.clone()), "Only the creator can claim creator fees.");
+
+			let owed = Self::creator_fees();
+			ensure!(!owed.is_zero(), "No creator fees to claim.");
+			ensure!(Self::_can_receive(&sender, owed), "Creator fees are below the existential deposit.");
+
+			<CreatorFees<T>>::kill();
+			<balances::Module<T>>::increase_free_balance_creating(&sender, owed);
+
+			Self::deposit_event(RawEvent::CreatorFeesClaimed(sender, owed));
+			Ok(())
+		}
+
+		/// Hands the creator's royalties and fee share to `new_creator`. Only allowed to
+		/// the creator.
+		pub fn transfer_creator(origin, new_creator: T::AccountId) -> Result {
+			let sender = ensure_signed(origin)?;
+			ensure!(Self::creator() == Some(sender.clone()), "Only the creator can hand over the role.");
+
+			<Creator<T>>::put(new_creator.clone());
+
+			Self::deposit_event(RawEvent::CreatorTransferred(sender, new_creator));
+			Ok(())
+		}
+
+		/// Switches the entry and exit tributes on or off without touching their rates.
+		pub fn set_fees_enabled(origin, enabled: bool) -> Result {
+			Self::_ensure_admin(origin)?;
+			<FeesEnabled<T>>::put(enabled);
+			Ok(())
+		}
+
+		/// Sets the spot price bounds trades may not cross. `None` removes a bound.
+		pub fn set_price_bounds(origin, min_price: Option<u128>, max_price: Option<u128>) -> Result {
+			Self::_ensure_admin(origin)?;
+			if let (Some(min), Some(max)) = (min_price, max_price) {
+				ensure!(min <= max, "Price floor is above the ceiling.");
+			}
+
+			match min_price {
+				Some(min) => <MinPrice<T>>::put(min),
+				None => <MinPrice<T>>::kill(),
+			}
+			match max_price {
+				Some(max) => <MaxPrice<T>>::put(max),
+				None => <MaxPrice<T>>::kill(),
+			}
+
+			Ok(())
+		}
+
+		/// Sets the most tokens a single account may buy in total. `None` removes the cap.
+		pub fn set_purchase_cap(origin, cap: Option<u128>) -> Result {
+			Self::_ensure_admin(origin)?;
+			match cap {
+				Some(cap) => <PurchaseCap<T>>::put(cap),
+				None => <PurchaseCap<T>>::kill(),
+			}
+			Ok(())
+		}
+
+		/// Sets the blocks an account must wait after buying before it can sell.
+		pub fn set_sell_cooldown(origin, cooldown: T::BlockNumber) -> Result {
+			Self::_ensure_admin(origin)?;
+			<SellCooldown<T>>::put(cooldown);
+			Ok(())
+		}
+
+		/// Sets the exit tribute in basis points.
+		pub fn set_exit_fee(origin, exit_fee: u32) -> Result {
+			Self::_ensure_admin(origin)?;
+			ensure!(exit_fee as u128 <= BPS, "Exit fee cannot exceed 100%.");
+			ensure!(exit_fee <= T::MAX_EXIT_FEE, "Exit fee is above the runtime maximum.");
+			<ExitFee<T>>::put(exit_fee);
+			Ok(())
+		}
+
+		/// Sets the progressive tribute schedule: a trade of at least the given share of
+		/// the supply pays the matching surcharge on top of the base tribute.
+		pub fn set_fee_tiers(origin, tiers: Vec<(u32, u32)>) -> Result {
+			Self::_ensure_admin(origin)?;
+			ensure!(tiers.len() <= MAX_FEE_TIERS, "Too many fee tiers.");
+			ensure!(tiers.iter().all(|(_, surcharge)| *surcharge as u128 <= BPS), "Fee surcharge cannot exceed 100%.");
+			<FeeTiers<T>>::put(tiers);
+			Ok(())
+		}
+
+		/// Sets the holding-duration schedule: an account that has held tokens for at
+		/// least the given blocks pays no more than the matching exit tribute.
+		pub fn set_loyalty_tiers(origin, tiers: Vec<(T::BlockNumber, u32)>) -> Result {
+			Self::_ensure_admin(origin)?;
+			ensure!(tiers.len() <= MAX_LOYALTY_TIERS, "Too many loyalty tiers.");
+			ensure!(tiers.iter().all(|(_, rate)| *rate as u128 <= BPS), "Exit tribute cannot exceed 100%.");
+			<LoyaltyTiers<T>>::put(tiers);
+			Ok(())
+		}
+
+		/// Sets the accounts entry and exit tributes are split between, in proportion to
+		/// their weights. An empty list sends tributes to the funding pool.
+		pub fn set_beneficiaries(origin, beneficiaries: Vec<(T::AccountId, u32)>) -> Result {
+			Self::_ensure_admin(origin)?;
+			ensure!(beneficiaries.len() <= MAX_BENEFICIARIES, "Too many beneficiaries.");
+			ensure!(beneficiaries.iter().all(|(_, weight)| *weight > 0), "Beneficiary weight must be positive.");
+			ensure!(
+				!beneficiaries.iter().enumerate().any(|(i, (who, _))| beneficiaries[..i].iter().any(|(b, _)| b == who)),
+				"Beneficiary is listed twice."
+			);
+			<Beneficiaries<T>>::put(beneficiaries);
+			Ok(())
+		}
+
+		/// Sets the share of every tribute set aside for the insurance fund, in basis
+		/// points.
+		pub fn set_insurance_share(origin, share: u32) -> Result {
+			Self::_ensure_admin(origin)?;
+			ensure!(share as u128 + Self::creator_share() as u128 <= BPS, "Insurance and creator shares cannot exceed 100%.");
+			<InsuranceShare<T>>::put(share);
+			Ok(())
+		}
+
+		/// Sets the share of every tribute owed to the creator, in basis points.
+		pub fn set_creator_share(origin, share: u32) -> Result {
+			Self::_ensure_admin(origin)?;
+			ensure!(share as u128 + Self::insurance_share() as u128 <= BPS, "Insurance and creator shares cannot exceed 100%.");
+			<CreatorShare<T>>::put(share);
+			Ok(())
+		}
+
+		/// Moves up to `amount` from the insurance fund into the reserve. Only allowed
+		/// while the reserve cannot cover selling the supply, and never beyond the
+		/// shortfall. Not timelocked, so the governor can act while the shortfall lasts.
+		pub fn draw_insurance(origin, #[compact] amount: T::Balance) -> Result {
+			Self::_ensure_governor(origin)?;
+			let owed = Self::curve_params().area(0, Self::_backed_supply(), true).unwrap_or(u128::max_value());
+			let shortfall = owed.saturating_sub(Self::_total_reserve());
+			ensure!(shortfall > 0, "Reserve is solvent.");
+			let amount = cmp::min(amount, Self::_saturating_balance(shortfall));
+			let fund = Self::insurance_fund().checked_sub(&amount).ok_or("Not enough in the insurance fund.")?;
+
+			<InsuranceFund<T>>::put(fund);
+			<Reserve<T>>::mutate(|reserve| *reserve += amount);
+
+			Self::deposit_event(RawEvent::InsuranceDrawn(amount, fund));
+			Self::_check_invariants(&[]);
+			Ok(())
+		}
+
+		/// Sets how many blocks bought tokens stay unsellable, zero to disable.
+		pub fn set_buy_lock_period(origin, period: T::BlockNumber) -> Result {
+			Self::_ensure_admin(origin)?;
+			<BuyLockPeriod<T>>::put(period);
+			Ok(())
+		}
+
+		/// Deploys `amount` of the liquid reserve to the reserve manager. Sells can only
+		/// be paid out of what stays liquid.
+		pub fn deploy_reserve(origin, #[compact] amount: T::Balance) -> Result {
+			Self::_ensure_admin(origin)?;
+			let liquid = Self::reserve().checked_sub(&amount).ok_or("Not enough liquid reserve.")?;
+			let deployed = Self::deployed_reserve().checked_add(&amount).ok_or("Overflow in deployed reserve.")?;
+
+			T::ReserveManager::deploy(amount)?;
+			<Reserve<T>>::put(liquid);
+			<DeployedReserve<T>>::put(deployed);
+
+			Self::deposit_event(RawEvent::ReserveDeployed(amount));
+			Self::_check_invariants(&[]);
+			Ok(())
+		}
+
+		/// Recalls up to `amount` from the reserve manager into the liquid reserve. Any
+		/// yield returned beyond the deployed principal also joins the reserve.
+		pub fn recall_reserve(origin, #[compact] amount: T::Balance) -> Result {
+			Self::_ensure_admin(origin)?;
+
+			let returned = T::ReserveManager::withdraw(amount);
+			let deployed = Self::deployed_reserve();
+			<DeployedReserve<T>>::put(deployed - cmp::min(returned, deployed));
+			<Reserve<T>>::mutate(|reserve| *reserve += returned);
+
+			Self::deposit_event(RawEvent::ReserveRecalled(returned));
+			Self::_check_invariants(&[]);
+			Ok(())
+		}
+
+		/// Initializes the token with constructor parameters: `price = slp * supply^exp + base_price`.
+		pub fn init(
+			origin,
+			exp: u128,
+			slp: u128,
+			#[compact] base_price: u128,
+			#[compact] max_supply: u128,
+			team: Option<TeamAllocation<T::AccountId, T::BlockNumber>>
+		) -> Result {
+			let creator = ensure_signed(origin)?;
+			let params = CurveParams { shape: CurveShape::Polynomial, exponent: exp, slope: slp, steepness: 0, base_price };
+			Self::_init_curve(creator, params, max_supply, team)
+		}
+
+		/// Initializes the token with a named curve scaled by `slope`.
+		pub fn init_with_preset(
+			origin,
+			preset: CurvePreset,
+			slope: u128,
+			#[compact] max_supply: u128,
+			team: Option<TeamAllocation<T::AccountId, T::BlockNumber>>
+		) -> Result {
+			let creator = ensure_signed(origin)?;
+			Self::_init_curve(creator, preset.params(slope)?, max_supply, team)
+		}
+
+	}
+}
+
+decl_event!(
+	/// An event in this module.
+	pub enum Event<T> where
+		AccountId = <T as system::Trait>::AccountId,
+		Hash = <T as system::Trait>::Hash,
+		BlockNumber = <T as system::Trait>::BlockNumber,
+		Balance = <T as balances::Trait>::Balance,
+		Moment = <T as timestamp::Trait>::Moment
+	{
 		// Event for transfer of tokens.
 		Transfer(Option<AccountId>, Option<AccountId>, u128),
 		// Event for approval.
 		Approval(AccountId, AccountId, u128),
+		// Event for every allowance of an owner revoked at once.
+		// <Owner, Revoked>
+		AllowancesRevoked(AccountId, u32),
 		// Event for buy of tokens.
-		// <Buyer, BuyAmount, Paid>
-		Buy(Option<AccountId>, u128, u128),
+		// <TradeId, Buyer, BuyAmount, Paid, Timestamp>
+		Buy(u64, Option<AccountId>, u128, u128, Moment),
 		// Event for sell of tokens.
-		// <Seller, SellAmount, Returned>
-		Sell(Option<AccountId>, u128, u128),
+		// <TradeId, Seller, SellAmount, Returned, Timestamp>
+		Sell(u64, Option<AccountId>, u128, u128, Moment),
+		// Event for a buy order entering the block queue.
+		// <Buyer, Tokens, MaxPrice>
+		BuyQueued(AccountId, u128, u128),
+		// Event for a sell order entering the block queue.
+		// <Seller, Tokens, MinPrice>
+		SellQueued(AccountId, u128, u128),
+		// Event for a buy and a sell netted against each other.
+		// <TradeId, Buyer, Seller, Tokens, Paid>
+		OrdersMatched(u64, AccountId, AccountId, u128, u128),
+		// Event for a resting order that was cancelled and returned.
+		// <Owner, UnfilledTokens>
+		OrderRefunded(AccountId, u128),
+		// Event for a keeper settling due orders.
+		// <Keeper, OrdersFilled, Bounty>
+		OrdersExecuted(AccountId, u32, Balance),
+		// Event for reserve deployed to the reserve manager.
+		// <Amount>
+		ReserveDeployed(Balance),
+		// Event for reserve returned by the reserve manager.
+		// <Amount>
+		ReserveRecalled(Balance),
+		// Event for a new recurring buy.
+		// <Id, Buyer, AmountPerInterval, Periods>
+		RecurringBuyScheduled(u64, AccountId, u128, u32),
+		// Event for one period of a recurring buy.
+		// <Id, Buyer, Tokens, Paid>
+		RecurringBuyExecuted(u64, AccountId, u128, u128),
+		// Event for a cancelled recurring buy.
+		// <Id, Buyer, Refunded>
+		RecurringBuyCancelled(u64, AccountId, u128),
+		// Event for a new balance snapshot.
+		// <SnapshotId>
+		Snapshot(u32),
+		// Event for a new claimable distribution.
+		// <DistributionId, Root, Total>
+		DistributionPosted(u32, Hash, u128),
+		// Event for a claimed share of a distribution.
+		// <DistributionId, Claimant, Amount>
+		Claimed(u32, AccountId, u128),
+		// Event for a transfer locked under a vesting schedule.
+		// <From, To, Amount>
+		VestedTransfer(AccountId, AccountId, u128),
+		// Event for a referrer rewarded out of an entry tribute.
+		// <Referrer, Buyer, Reward>
+		ReferralRewarded(AccountId, AccountId, u128),
+		// Event for a beneficiary claiming its share of tributes.
+		// <Beneficiary, Amount>
+		BeneficiaryShareClaimed(AccountId, u128),
+		// Event for a referrer claiming its referral rewards.
+		// <Referrer, Amount>
+		ReferralRewardsClaimed(AccountId, u128),
+		// Event for the creator claiming transfer royalties.
+		// <Creator, Amount>
+		RoyaltiesClaimed(AccountId, u128),
+		// Event for the creator claiming its share of tributes.
+		// <Creator, Amount>
+		CreatorFeesClaimed(AccountId, Balance),
+		// Event for the creator role handed to a new account.
+		// <OldCreator, NewCreator>
+		CreatorTransferred(AccountId, AccountId),
+		// Event for tokens redeemed for a share of the reserve.
+		// <Redeemer, Tokens, Paid>
+		Redeemed(AccountId, u128, Balance),
+		// Event for the supply realigned with the oracle price.
+		// <OraclePrice, Minted, Burned>
+		Rebalanced(u128, u128, u128),
+		// Event for a scheduled curve change taking effect.
+		// <Slope, Exponent>
+		CurveStepped(u128, u128),
+		// Event for sells paused after taking too much of the reserve in a window.
+		// <SoldInWindow, Reserve, PausedUntil>
+		CircuitBroken(u128, u128, BlockNumber),
+		// Event for sells resumed by governance before the cooldown ended.
+		// <PausedUntil>
+		SellsResumed(BlockNumber),
+		// Event for trading paused after the end-of-block solvency check failed.
+		// <Reason>
+		EmergencyPaused(Vec<u8>),
+		// Event for trading resumed by governance.
+		// <PausedAt>
+		TradingResumed(BlockNumber),
+		// Event for a trade leaving the reserve ratio below an alert threshold.
+		// <Ratio>
+		ReserveRatioLow(u32),
+		// Event for tokens bought back with the funding pool and burned.
+		// <Tokens, Cost>
+		Buyback(u128, u128),
+		// Event for the slope controller changing the slope.
+		// <OldSlope, NewSlope>
+		SlopeTuned(u128, u128),
+		// Event for inflation minted at the end of an era.
+		// <Beneficiary, Amount>
+		InflationMinted(AccountId, u128),
+		// Event for the team allocation minted at init.
+		// <Team, Amount>
+		TeamAllocated(AccountId, u128),
+		// Event for the total supply reset to the tokens held.
+		// <OldSupply, NewSupply>
+		SupplyReconciled(u128, u128),
+		// Event for the reserve reset to the curve obligation.
+		// <Account, OldReserve, NewReserve>
+		ReserveReconciled(AccountId, u128, u128),
+		// Event for the metadata URI set or cleared.
+		// <Uri>
+		MetadataUriChanged(Option<Vec<u8>>),
+		// Event for governance handed to a new admin.
+		// <OldAdmin, NewAdmin>
+		AdminTransferred(Option<AccountId>, AccountId),
+		// Event for governance renounced.
+		// <OldAdmin>
+		AdminRenounced(Option<AccountId>),
+		// Event for a parameter update entering the timelock.
+		// <UpdateId, DueBlock>
+		UpdateQueued(u64, BlockNumber),
+		// Event for a queued parameter update cancelled before it applied.
+		// <UpdateId>
+		UpdateCancelled(u64),
+		// Event for a queued parameter update reaching the end of the timelock.
+		// <UpdateId, Applied>
+		UpdateApplied(u64, bool),
+		// Event for tokens locked behind a veto of a queued update.
+		// <UpdateId, Holder, Tokens>
+		Vetoed(u64, AccountId, u128),
+		// Event for a queued update cancelled by holder vetoes when it fell due.
+		// <UpdateId, VetoTally>
+		UpdateVetoed(u64, u128),
+		// Event for tokens escrowed into a stream.
+		// <StreamId, From, To, Total>
+		StreamOpened(u64, AccountId, AccountId, u128),
+		// Event for released stream tokens withdrawn by the recipient.
+		// <StreamId, To, Amount>
+		StreamWithdrawn(u64, AccountId, u128),
+		// Event for a stream closed early.
+		// <StreamId, PaidToRecipient, Refunded>
+		StreamCancelled(u64, u128, u128),
+		// Event for tokens held in escrow.
+		// <EscrowId, From, To, Amount>
+		EscrowOpened(u64, AccountId, AccountId, u128),
+		// Event for an escrow paid to its recipient.
+		// <EscrowId, To, Amount>
+		EscrowReleased(u64, AccountId, u128),
+		// Event for an escrow returned to its funder.
+		// <EscrowId, From, Amount>
+		EscrowRefunded(u64, AccountId, u128),
+		// Event for a cosigning policy set.
+		// <Who, Cosigner, Threshold>
+		CosignerSet(AccountId, AccountId, u128),
+		// Event for a cosigning policy removed by the cosigner.
+		// <Who>
+		CosignerRemoved(AccountId),
+		// Event for a spend limit set on an account.
+		// <Who, Amount, Window>
+		SpendLimitSet(AccountId, u128, BlockNumber),
+		// Event for a self-imposed spend limit that stops applying at a later block.
+		// <Who, LiftsAt>
+		SpendLimitLifting(AccountId, BlockNumber),
+		// Event for a spend limit removed by the admin.
+		// <Who>
+		SpendLimitRemoved(AccountId),
+		// Event for an account turning reversible transfers on or off.
+		// <Who, Guardian>
+		ReversibleTransfersSet(AccountId, Option<AccountId>),
+		// Event for a transfer held for its dispute window.
+		// <ReversibleTransferId, From, To, Amount, FinalizesAt>
+		TransferHeld(u64, AccountId, AccountId, u128, BlockNumber),
+		// Event for a reversible transfer returned to its sender.
+		// <ReversibleTransferId>
+		TransferReversed(u64),
+		// Event for a transfer held until the cosigner approves it.
+		// <PendingTransferId, From, To, Amount>
+		TransferPending(u64, AccountId, AccountId, u128),
+		// Event for a held transfer approved by the cosigner.
+		// <PendingTransferId>
+		TransferApproved(u64),
+		// Event for a held transfer returned to its sender.
+		// <PendingTransferId>
+		TransferCancelled(u64),
+		// Event for tokens locked under a hash.
+		// <Hash, From, To, Amount>
+		HtlcLocked(Hash, AccountId, AccountId, u128),
+		// Event for a hashed timelock claimed, revealing the preimage.
+		// <Hash, Preimage>
+		HtlcClaimed(Hash, Vec<u8>),
+		// Event for a timed out hashed timelock refunded.
+		// <Hash, From, Amount>
+		HtlcRefunded(Hash, AccountId, u128),
+		// Event for a resting order earning a rebate when matched.
+		// <Maker, Rebate>
+		MakerRebateEarned(AccountId, u128),
+		// Event for maker rebates paid out.
+		// <Maker, Amount>
+		MakerRebatesClaimed(AccountId, u128),
+		// Event for tokens moved into stake.
+		// <Who, Tokens>
+		Staked(AccountId, u128),
+		// Event for a flash mint repaid with its fee.
+		// <Borrower, Tokens, Fee>
+		FlashMintRepaid(AccountId, u128, Balance),
+		// Event for a flash mint left partly unpaid, with the collateral kept to back it.
+		// <Borrower, Tokens, Kept>
+		FlashMintDefaulted(AccountId, u128, Balance),
+		// Event for tokens freed from stake.
+		// <Who, Tokens>
+		Unstaked(AccountId, u128),
+		// Event for tokens locked as collateral.
+		// <Who, Tokens>
+		CollateralLocked(AccountId, u128),
+		// Event for tokens freed from collateral.
+		// <Who, Tokens>
+		CollateralUnlocked(AccountId, u128),
+		// Event for collateral sold on the curve.
+		// <Who, Tokens, Proceeds>
+		CollateralLiquidated(AccountId, u128, u128),
+		// Event for tokens burned as a penalty.
+		// <Who, Tokens, Insured>
+		Slashed(AccountId, u128, Balance),
+		// Event for the insurance fund topping up the reserve.
+		// <Amount, FundLeft>
+		InsuranceDrawn(Balance, Balance),
+		// Event for the periodic solvency report, the ratio in basis points.
+		// <Reserve, Obligation, Ratio>
+		SolvencyReport(u128, u128, u32),
+		// Event for old candles and trade history removed.
+		// <Entries>
+		HistoryPruned(u32),
+		// Event for voting power delegated.
+		// <Delegator, Delegate, Tokens>
+		Delegated(AccountId, AccountId, u128),
+		// Event for a delegation withdrawn.
+		// <Delegator, Delegate, Tokens>
+		Undelegated(AccountId, AccountId, u128),
+		// Event for a sell payout queued until the reserve is liquid.
+		// <Payee, Payout>
+		PayoutQueued(AccountId, Balance),
+		// Event for a queued sell payout made.
+		// <Payee, Payout>
+		PayoutPaid(AccountId, Balance),
+		// Event for a pre-sale auction opening.
+		// <Tranche, StartPrice, FloorPrice>
+		AuctionStarted(u128, u128, u128),
+		// Event for a bid in the pre-sale auction.
+		// <Bidder, Amount>
+		AuctionBid(AccountId, u128),
+		// Event for the pre-sale auction closing and the curve opening.
+		// <ClearingPrice, TokensSold>
+		AuctionClosed(u128, u128),
+	}
+);
+
+/// All functions in the decl_module macro are part of the public interface of the module.
+impl<T: Trait> Module<T> {
+	/// Internal transfer function for ERC20 token. `royalty` is taken from the sender
+	/// on top of `value` and held for the creator.
+	fn _transfer(from: T::AccountId, to: T::AccountId, value: u128, royalty: u128) -> Result {
+		ensure!(
+			Self::_has_balance_entry(&from),
+			"Account does not own any token."
+		);
+
+		let debit = value.checked_add(royalty).ok_or("Overflow in calculating royalty.")?;
+		let sender_balance = Self::balance_of(from.clone());
+		ensure!(
+			sender_balance >= debit,
+			"Not enough balance."
+		);
+		ensure!(
+			sender_balance - Self::_locked(&from) >= debit,
+			"Tokens are locked by vesting or a veto."
+		);
+
+		let updated_from_balance = sender_balance.checked_sub(debit).ok_or("Underflow in calculating balance.")?;
+		let receiver_balance = if from == to { updated_from_balance } else { Self::balance_of(to.clone()) };
+		let updated_to_balance = receiver_balance.checked_add(value).ok_or("Overflow in calculating balance.")?;
+		Self::royalties_accrued().checked_add(royalty).ok_or("Overflow in accrued royalties.")?;
+
+		// Insert the updated balances into storage.
+		Self::_set_balance(&from, updated_from_balance);
+		Self::_set_balance(&to, updated_to_balance);
+		<RoyaltiesAccrued<T>>::mutate(|accrued| *accrued += royalty);
+
+		Self::deposit_event(RawEvent::Transfer(Some(from), Some(to), value));
+		Ok(())
+	}
+
+	/// Cosigner a transfer of `value` from `who` must wait for, if any.
+	fn _cosigner_for(who: &T::AccountId, value: u128) -> Option<T::AccountId> {
+		Self::cosigner(who).and_then(|(cosigner, threshold)| if value > threshold { Some(cosigner) } else { None })
+	}
+
+	/// Sends `value` tokens to `to` as `outflow`, or holds them until the sender's
+	/// cosigner approves if they are above its threshold, or for the dispute window if
+	/// the sender has reversible transfers on. Every movement of tokens out of an
+	/// account that can wait goes through here, and counts against the spend limit.
+	/// The royalty is charged either way.
+	fn _send(
+		from: T::AccountId,
+		to: T::AccountId,
+		value: u128,
+		royalty: u128,
+		outflow: Outflow<T::AccountId, T::Hash, T::BlockNumber>
+	) -> Result {
+		Self::_ensure_can_open(&outflow)?;
+		let cosigner = Self::_cosigner_for(&from, value);
+		let reversible = Self::account(&from).reversible;
+		if cosigner.is_none() && reversible.is_none() {
+			return Self::_with_spend_limit(&from, value, || match outflow {
+				Outflow::Transfer => Self::_transfer(from.clone(), to, value, royalty),
+				outflow => {
+					Self::_escrow(&from, value)?;
+					Self::_open(from.clone(), to, value, outflow);
+					Ok(())
+				}
+			});
+		}
+		let limit = Self::_spend_limit_after(&from, value)?;
+		let debit = value.checked_add(royalty).ok_or("Overflow in calculating royalty.")?;
+		Self::royalties_accrued().checked_add(royalty).ok_or("Overflow in accrued royalties.")?;
+
+		if let Some(cosigner) = cosigner {
+			let id = Self::next_pending_transfer_id();
+			let next_id = id.checked_add(1).ok_or("Overflow in pending transfer identifier.")?;
+			Self::_hold(&from, debit, royalty, limit)?;
+			<NextPendingTransferId<T>>::put(next_id);
+			<PendingTransfers<T>>::insert(id, PendingTransfer { from: from.clone(), to: to.clone(), cosigner, amount: value, outflow });
+			Self::deposit_event(RawEvent::TransferPending(id, from, to, value));
+		} else if let Some((guardian, period)) = reversible {
+			let id = Self::next_reversible_transfer_id();
+			let next_id = id.checked_add(1).ok_or("Overflow in reversible transfer identifier.")?;
+			let finalizes_at = <system::Module<T>>::block_number() + period;
+			ensure!(Self::reversible_transfers_due(finalizes_at).len() < MAX_REVERSIBLE_DUE, "Too many transfers complete at that block.");
+			Self::_hold(&from, debit, royalty, limit)?;
+			<NextReversibleTransferId<T>>::put(next_id);
+			<ReversibleTransfers<T>>::insert(id, ReversibleTransfer { from: from.clone(), to: to.clone(), guardian, amount: value, finalizes_at, outflow });
+			<ReversibleTransfersDue<T>>::mutate(finalizes_at, |ids| ids.push(id));
+			Self::deposit_event(RawEvent::TransferHeld(id, from, to, value, finalizes_at));
+		}
+		Ok(())
+	}
+
+	/// Checks `outflow` can be opened now.
+	fn _ensure_can_open(outflow: &Outflow<T::AccountId, T::Hash, T::BlockNumber>) -> Result {
+		let now = <system::Module<T>>::block_number();
+		match outflow {
+			Outflow::Transfer => {}
+			Outflow::Escrow(_, deadline) => {
+				ensure!(*deadline > now, "Escrow deadline has passed.");
+				Self::next_escrow_id().checked_add(1).ok_or("Overflow in escrow identifier.")?;
+			}
+			Outflow::Stream(..) => {
+				Self::next_stream_id().checked_add(1).ok_or("Overflow in stream identifier.")?;
+			}
+			Outflow::Htlc(hash, timeout) => {
+				ensure!(*timeout > now, "Hashed timelock timeout has passed.");
+				ensure!(!<Htlcs<T>>::exists(hash), "Hash is already locked.");
+			}
+		}
+		Ok(())
+	}
+
+	/// Pays `amount` escrowed tokens of `from` out to `to` as `outflow`, which must have
+	/// been checked with `_ensure_can_open`. Tokens for an escrow, stream or hashed
+	/// timelock stay escrowed under it.
+	fn _open(from: T::AccountId, to: T::AccountId, amount: u128, outflow: Outflow<T::AccountId, T::Hash, T::BlockNumber>) {
+		match outflow {
+			Outflow::Transfer => {
+				Self::_release(&to, amount);
+				Self::deposit_event(RawEvent::Transfer(Some(from), Some(to), amount));
+			}
+			Outflow::Escrow(arbiter, deadline) => {
+				let id = Self::next_escrow_id();
+				<NextEscrowId<T>>::put(id + 1);
+				<Escrows<T>>::insert(id, Escrow { from: from.clone(), to: to.clone(), arbiter, amount, deadline });
+				Self::deposit_event(RawEvent::EscrowOpened(id, from, to, amount));
+			}
+			Outflow::Stream(rate, duration) => {
+				let id = Self::next_stream_id();
+				let start = <system::Module<T>>::block_number();
+				<NextStreamId<T>>::put(id + 1);
+				<Streams<T>>::insert(id, Stream { from: from.clone(), to: to.clone(), rate, start, end: start + duration, withdrawn: 0 });
+				Self::deposit_event(RawEvent::StreamOpened(id, from, to, amount));
+			}
+			Outflow::Htlc(hash, timeout) => {
+				<Htlcs<T>>::insert(hash, Htlc { from: from.clone(), to: to.clone(), amount, timeout });
+				Self::deposit_event(RawEvent::HtlcLocked(hash, from, to, amount));
+			}
+		}
+	}
+
+	/// Pays out held tokens as `outflow`, or returns them to `from` if it can no longer
+	/// be opened, e.g. because its deadline passed while held. Returns whether it was
+	/// opened.
+	fn _complete(from: T::AccountId, to: T::AccountId, amount: u128, outflow: Outflow<T::AccountId, T::Hash, T::BlockNumber>) -> bool {
+		if Self::_ensure_can_open(&outflow).is_err() {
+			Self::_release(&from, amount);
+			return false;
+		}
+		Self::_open(from, to, amount, outflow);
+		true
+	}
+
+	/// Checks a transfer of `value` from `who` can be made at once, for transfers that
+	/// cannot wait for a cosigner or a dispute window.
+	fn _ensure_not_held(who: &T::AccountId, value: u128) -> Result {
+		ensure!(Self::_cosigner_for(who, value).is_none(), "Transfer needs the cosigner's approval.");
+		ensure!(Self::account(who).reversible.is_none(), "Transfers from this account are reversible.");
+		Ok(())
+	}
+
+	/// Escrows `debit` tokens of `who` for a held transfer, moving the `royalty` part
+	/// of it to the accrued royalties and counting it against the spend limit.
+	fn _hold(who: &T::AccountId, debit: u128, royalty: u128, limit: Option<SpendLimit<T::BlockNumber>>) -> Result {
+		Self::_escrow(who, debit)?;
+		<EscrowedTokens<T>>::mutate(|escrowed| *escrowed -= royalty);
+		<RoyaltiesAccrued<T>>::mutate(|accrued| *accrued += royalty);
+		Self::_mutate_account(who, |account| account.spend_limit = limit);
+		Ok(())
+	}
+
+	/// Pays out a reversible transfer whose dispute window has ended, unless it was
+	/// reversed. An escrow, stream or hashed timelock that can no longer be opened is
+	/// returned to the sender as if reversed.
+	fn _finalize_reversible_transfer(id: u64) {
+		let transfer = match <ReversibleTransfers<T>>::take(id) {
+			Some(transfer) => transfer,
+			None => return,
+		};
+		if !Self::_complete(transfer.from, transfer.to, transfer.amount, transfer.outflow) {
+			Self::deposit_event(RawEvent::TransferReversed(id));
+		}
+	}
+
+	/// The spend limit of `who` once `amount` more tokens leave the account, failing if
+	/// that exceeds it. A window that has ended starts over and a lifted limit that has
+	/// lapsed is dropped.
+	fn _spend_limit_after(who: &T::AccountId, amount: u128) -> rstd::result::Result<Option<SpendLimit<T::BlockNumber>>, &'static str> {
+		let mut limit = match Self::spend_limit(who) {
+			Some(limit) => limit,
+			None => return Ok(None),
+		};
+		let now = <system::Module<T>>::block_number();
+		if limit.lifts_at.map_or(false, |at| now >= at) {
+			return Ok(None);
+		}
+		if now >= limit.window_start + limit.window {
+			limit.window_start = now;
+			limit.spent = 0;
+		}
+		limit.spent = limit.spent.checked_add(amount)
+			.filter(|spent| *spent <= limit.amount)
+			.ok_or("Spend limit reached for this window.")?;
+		Ok(Some(limit))
+	}
+
+	/// Runs `f`, which moves `amount` tokens out of the account of `who`, if the spend
+	/// limit allows it, and counts them against the limit if it succeeds.
+	fn _with_spend_limit<F: FnOnce() -> Result>(who: &T::AccountId, amount: u128, f: F) -> Result {
+		let limit = Self::_spend_limit_after(who, amount)?;
+		f()?;
+		Self::_mutate_account(who, |account| account.spend_limit = limit);
+		Ok(())
+	}
+
+	/// Balance of `who` at snapshot `id`, or `None` if that snapshot has not been taken.
+	pub fn balance_at(who: &T::AccountId, id: u32) -> Option<u128> {
+		if id == 0 || id > Self::snapshot_id() {
+			return None;
+		}
+		Some(Self::_value_at(&Self::balance_checkpoints(who), id).unwrap_or_else(|| Self::balance_of(who)))
+	}
+
+	/// Total supply at snapshot `id`, or `None` if that snapshot has not been taken.
+	pub fn total_supply_at(id: u32) -> Option<u128> {
+		if id == 0 || id > Self::snapshot_id() {
+			return None;
+		}
+		Some(Self::_value_at(&Self::supply_checkpoints(), id).unwrap_or_else(Self::total_supply))
+	}
+
+	/// The first checkpoint taken at or after snapshot `id` holds the value at `id`.
+	fn _value_at(checkpoints: &[(u32, u128)], id: u32) -> Option<u128> {
+		checkpoints.iter().find(|(snapshot, _)| *snapshot >= id).map(|(_, value)| *value)
+	}
+
+	/// Folds a Merkle proof into a root. Each pair is hashed in byte order, so proofs
+	/// do not need to say which side a sibling is on.
+	fn _merkle_root(leaf: T::Hash, proof: &[T::Hash]) -> T::Hash {
+		proof.iter().fold(leaf, |node, sibling| {
+			if node.as_ref() <= sibling.as_ref() {
+				T::Hashing::hash_of(&(node, *sibling))
+			} else {
+				T::Hashing::hash_of(&(*sibling, node))
+			}
+		})
+	}
+
+	/// Writes an account's token balance, checkpointing the old one first. A zero
+	/// balance removes the entry rather than storing an explicit zero.
+	fn _set_balance(who: &T::AccountId, balance: u128) {
+		Self::_checkpoint_balance(who);
+
+		let old = Self::balance_of(who);
+		if balance > old {
+			let now = <system::Module<T>>::block_number();
+			Self::_mutate_account(who, |account| {
+				account.held_since = Some(Self::_entry_block(account.held_since, old, balance, now));
+			});
+		} else if old > 0 && balance == 0 {
+			Self::_mutate_account(who, |account| account.held_since = None);
+		}
+
+		// Only balances under the hashed key are counted. Legacy balances from before the
+		// upgrade are counted when they move over.
+		let key = T::Hashing::hash_of(who);
+		let counted = <Balances<T>>::get(&key);
+		if counted == 0 && balance > 0 {
+			<HolderCount<T>>::mutate(|count| *count += 1);
+		} else if counted > 0 && balance == 0 {
+			<HolderCount<T>>::mutate(|count| *count = count.saturating_sub(1));
+		}
+		<BalanceTotal<T>>::mutate(|total| *total = total.saturating_sub(counted).saturating_add(balance));
+
+		let legacy = <BalanceOf<T>>::take(who);
+		if legacy > 0 {
+			<LegacyBalanceTotal<T>>::mutate(|total| *total = total.saturating_sub(legacy));
+		}
+		if balance == 0 {
+			<Balances<T>>::remove(key);
+		} else {
+			<Balances<T>>::insert(key, balance);
+		}
+	}
+
+	/// Token balance of `who`.
+	pub fn balance_of<K: Borrow<T::AccountId>>(who: K) -> u128 {
+		let who = who.borrow();
+		// Nonzero balances are only ever stored under one of the two keys.
+		match <Balances<T>>::get(T::Hashing::hash_of(who)) {
+			0 => Self::legacy_balance_of(who),
+			balance => balance,
+		}
+	}
+
+	/// Whether `who` has a balance stored, even a zero one.
+	fn _has_balance_entry(who: &T::AccountId) -> bool {
+		<Balances<T>>::exists(T::Hashing::hash_of(who)) || <BalanceOf<T>>::exists(who)
+	}
+
+	/// Tokens `spender` may transfer from `owner`, given as `(owner, spender)`.
+	pub fn allowance(key: (T::AccountId, T::AccountId)) -> u128 {
+		let hash = T::Hashing::hash_of(&key);
+		if <Allowances<T>>::exists(hash) {
+			<Allowances<T>>::get(hash)
+		} else {
+			Self::legacy_allowance(key)
+		}
+	}
+
+	/// Whether an allowance is stored for `(owner, spender)`, even a zero one.
+	fn _has_allowance_entry(key: (T::AccountId, T::AccountId)) -> bool {
+		<Allowances<T>>::exists(T::Hashing::hash_of(&key)) || <Allowance<T>>::exists(key)
+	}
+
+	/// Stores or, with `None`, removes the allowance of `(owner, spender)`, dropping
+	/// any entry stored under the old layout.
+	fn _set_allowance(key: (T::AccountId, T::AccountId), allowance: Option<u128>) {
+		let hash = T::Hashing::hash_of(&key);
+		<Allowance<T>>::remove(key);
+		match allowance {
+			Some(allowance) => <Allowances<T>>::insert(hash, allowance),
+			None => <Allowances<T>>::remove(hash),
+		}
+	}
+
+	/// Applies `f` to the state of `who`, removing the entry once nothing is left in it.
+	fn _mutate_account<F: FnOnce(&mut AccountData<T::AccountId, T::BlockNumber>)>(who: &T::AccountId, f: F) {
+		let mut account = Self::account(who);
+		f(&mut account);
+		if account == AccountData::default() {
+			<Accounts<T>>::remove(who);
+		} else {
+			<Accounts<T>>::insert(who, account);
+		}
+	}
+
+	/// Vesting schedules restricting how much of the balance of `who` can move.
+	pub fn vesting<K: Borrow<T::AccountId>>(who: K) -> Vec<VestingSchedule<T::BlockNumber>> {
+		Self::account(who).vesting
+	}
+
+	/// Bought lots of `who` that cannot be sold yet, as (unlock block, tokens).
+	pub fn buy_locks<K: Borrow<T::AccountId>>(who: K) -> Vec<(T::BlockNumber, u128)> {
+		Self::account(who).buy_locks
+	}
+
+	/// Block of the latest buy of `who`.
+	pub fn last_buy<K: Borrow<T::AccountId>>(who: K) -> Option<T::BlockNumber> {
+		Self::account(who).last_buy
+	}
+
+	/// Tokens `who` has bought in total.
+	pub fn purchased<K: Borrow<T::AccountId>>(who: K) -> u128 {
+		Self::account(who).purchased
+	}
+
+	/// Currency `who` has spent buying, if cost basis is tracked.
+	pub fn total_spent<K: Borrow<T::AccountId>>(who: K) -> u128 {
+		Self::account(who).total_spent
+	}
+
+	/// Currency `who` has received selling, if cost basis is tracked.
+	pub fn total_proceeds<K: Borrow<T::AccountId>>(who: K) -> u128 {
+		Self::account(who).total_proceeds
+	}
+
+	/// Tokens of `who` locked behind vetoes.
+	pub fn veto_locked<K: Borrow<T::AccountId>>(who: K) -> u128 {
+		Self::account(who).veto_locked
+	}
+
+	/// Tokens of `who` held as stake.
+	pub fn staked<K: Borrow<T::AccountId>>(who: K) -> u128 {
+		Self::account(who).staked
+	}
+
+	/// Tokens of `who` locked as collateral.
+	pub fn collateral<K: Borrow<T::AccountId>>(who: K) -> u128 {
+		Self::account(who).collateral
+	}
+
+	/// Cosigner of `who` and the threshold above which transfers wait for it.
+	pub fn cosigner<K: Borrow<T::AccountId>>(who: K) -> Option<(T::AccountId, u128)> {
+		Self::account(who).cosigner
+	}
+
+	/// Limit on the tokens `who` may transfer or sell per window.
+	pub fn spend_limit<K: Borrow<T::AccountId>>(who: K) -> Option<SpendLimit<T::BlockNumber>> {
+		Self::account(who).spend_limit
+	}
+
+	/// Delegate `who` delegated its voting power to, with the tokens delegated.
+	pub fn delegation<K: Borrow<T::AccountId>>(who: K) -> Option<(T::AccountId, u128)> {
+		Self::account(who).delegation
+	}
+
+	/// Tokens accounted for outside the total supply: balances, tokens held by queued
+	/// sells, unclaimed royalties, unclaimed auction purchases and tokens held by
+	/// streams, escrows, hashed timelocks and pending transfers, less flash minted
+	/// tokens not yet burned.
+	fn _held_supply() -> u128 {
+		let queued = Self::sell_queue().iter().fold(0u128, |total, order| total.saturating_add(order.tokens));
+		Self::balance_total().saturating_add(Self::legacy_balance_total())
+			.saturating_add(queued).saturating_add(Self::royalties_accrued())
+			.saturating_add(Self::auction_unclaimed())
+			.saturating_add(Self::escrowed_tokens())
+			.saturating_sub(Self::flash_minted())
+	}
+
+	/// Seeds `LegacyBalanceTotal` on a chain upgraded with holders. Balances under the
+	/// legacy key cannot be iterated, so they are taken to be whatever part of the
+	/// supply the tracked holdings do not account for.
+	fn _seed_legacy_total() {
+		<LegacyBalanceTotal<T>>::put(Self::total_supply().saturating_sub(Self::_held_supply()));
+		<LegacyTotalSeeded<T>>::put(true);
+	}
+
+	/// Takes `amount` of the unlocked tokens of `who` into escrow.
+	fn _escrow(who: &T::AccountId, amount: u128) -> Result {
+		let free = Self::balance_of(who).saturating_sub(Self::_locked(who));
+		ensure!(free >= amount, "Not enough unlocked balance.");
+		Self::_set_balance(who, Self::balance_of(who) - amount);
+		<EscrowedTokens<T>>::mutate(|escrowed| *escrowed += amount);
+		Ok(())
+	}
+
+	/// Pays `amount` of escrowed tokens to `to`.
+	fn _release(to: &T::AccountId, amount: u128) {
+		<EscrowedTokens<T>>::mutate(|escrowed| *escrowed -= amount);
+		Self::_set_balance(to, Self::balance_of(to).saturating_add(amount));
+	}
+
+	/// Checks the total supply matches the tokens actually held.
+	pub fn audit_supply() -> Result {
+		ensure!(Self::total_supply() == Self::_held_supply(), "Total supply does not match the tokens held.");
+		Ok(())
+	}
+
+	/// Checks the supply audit and that the reserve covers what sellers of the backed
+	/// supply are owed.
+	pub fn audit_solvency() -> Result {
+		Self::audit_supply()?;
+		let owed = Self::curve_params().area(0, Self::_backed_supply(), false).unwrap_or(u128::max_value());
+		ensure!(Self::_total_reserve() >= owed, "Reserve cannot cover what sellers of the supply are owed.");
+		Ok(())
+	}
+
+	/// Re-checks the module's invariants, panicking with a description of the first
+	/// one violated. The allowance index is checked for each of `owners`.
+	///
+	/// Inflationary supply and the unvested team allocation are excluded from solvency.
+	/// Tokens minted outside the curve by `claim` and `rebalance` are backed from the
+	/// funding pool.
+	#[cfg(feature = "debug-invariants")]
+	fn _check_invariants(owners: &[&T::AccountId]) {
+		if let Err(e) = Self::audit_supply() {
+			panic!("{}", e);
+		}
+
+		let reserve = Self::_total_reserve();
+		let owed = Self::curve_params().area(0, Self::_backed_supply(), false).unwrap_or(u128::max_value());
+		if reserve < owed {
+			panic!("Reserve {} cannot cover {} owed to sellers of the supply.", reserve, owed);
+		}
+
+		for owner in owners {
+			let spenders = Self::spenders(*owner);
+			if spenders.len() as u32 > T::MAX_ALLOWANCES {
+				panic!("Owner has {} allowances, above the maximum.", spenders.len());
+			}
+			if spenders.iter().enumerate().any(|(i, (spender, _))| spenders[..i].iter().any(|(s, _)| s == spender)) {
+				panic!("Owner has a spender indexed twice.");
+			}
+			let deposits = spenders.iter().fold(<T::Balance>::zero(), |total, (_, deposit)| total + *deposit);
+			if <balances::Module<T>>::reserved_balance(*owner) < deposits {
+				panic!("Owner has less reserved than its allowance deposits.");
+			}
+		}
+	}
+
+	#[cfg(not(feature = "debug-invariants"))]
+	fn _check_invariants(_owners: &[&T::AccountId]) {}
+
+	/// Writes the total supply, checkpointing the old one first.
+	fn _set_supply(supply: u128) {
+		let id = Self::snapshot_id();
+		if id > 0 {
+			let mut checkpoints = Self::supply_checkpoints();
+			if checkpoints.last().map_or(true, |(last, _)| *last < id) {
+				checkpoints.push((id, Self::total_supply()));
+				<SupplyCheckpoints<T>>::put(checkpoints);
+			}
+		}
+		<TotalSupply<T>>::put(supply);
+		<SpotPrice<T>>::put(Self::price_at(supply));
+	}
+
+	/// Records the balance of `who` for the latest snapshot if it has not been yet.
+	fn _checkpoint_balance(who: &T::AccountId) {
+		let id = Self::snapshot_id();
+		if id == 0 {
+			return;
+		}
+
+		let mut checkpoints = Self::balance_checkpoints(who);
+		if checkpoints.last().map_or(true, |(last, _)| *last < id) {
+			checkpoints.push((id, Self::balance_of(who)));
+			<BalanceCheckpoints<T>>::insert(who, checkpoints);
+		}
+	}
+
+	/// Internal buy function: `payer` pays the curve cost plus the entry tribute and
+	/// the tokens are minted to `beneficiary`.
+	fn _buy(payer: T::AccountId, beneficiary: T::AccountId, tokens: u128, max_cost: Option<u128>, referrer: Option<T::AccountId>) -> Result {
+		// Storage is not rolled back when a dispatch fails, so everything that can fail
+		// is checked before the first write.
+		let (cost, fee) = Self::_check_buy(&beneficiary, tokens, max_cost)?;
+		let total_ = Self::_to_balance(cost + fee)?;
+		Self::_ensure_can_withdraw(&payer, total_)?;
+
+		Self::_withdraw(&payer, total_)?;
+		Self::_fill_buy(&payer, beneficiary, tokens, cost, fee, referrer)
+	}
+
+	/// Completes a buy checked by `_check_buy` whose cost and tribute `payer` has paid:
+	/// adds the cost to the reserve, splits the tribute, mints the tokens and records
+	/// the trade.
+	fn _fill_buy(payer: &T::AccountId, beneficiary: T::AccountId, tokens: u128, cost: u128, fee: u128, referrer: Option<T::AccountId>) -> Result {
+		<Reserve<T>>::mutate(|reserve| *reserve += Self::_saturating_balance(cost));
+		Self::_collect_entry_fee(payer, fee, referrer);
+
+		Self::_mint(beneficiary.clone(), tokens)?;
+
+		Self::_record_volume(cost);
+		Self::_check_reserve_ratio();
+		let trade_id = Self::_next_trade_id();
+		Self::_record_trade(trade_id, &beneficiary, TradeSide::Buy, tokens, cost);
+		Self::deposit_event(RawEvent::Buy(trade_id, Some(beneficiary), tokens, cost, <timestamp::Module<T>>::now()));
+
+		Ok(())
+	}
+
+	/// Checks a buy of `tokens` for `beneficiary` can go ahead, short of the payer's
+	/// funds, returning its curve cost and entry tribute.
+	fn _check_buy(beneficiary: &T::AccountId, tokens: u128, max_cost: Option<u128>) -> rstd::result::Result<(u128, u128), &'static str> {
+		ensure!(!Self::auction_open(), "The curve opens after the pre-sale.");
+		ensure!(Self::flash_minted() == 0, "The curve takes no buys during a flash mint.");
+		ensure!(!Self::paused(), "Trading is paused.");
+		ensure!(tokens >= T::MIN_TRADE, "Buy is below the minimum trade size.");
+		let cost = Self::cost_to_buy(tokens).ok_or("Overflow while buying tokens.")?;
+		ensure!(Self::_price_allowed(TradeSide::Buy, tokens), "Buy would push the price above the ceiling.");
+		ensure!(Self::_within_purchase_cap(beneficiary, tokens), "Purchase would exceed the per-account cap.");
+		let cost_ = Self::_to_balance(cost)?;
+		let fee = Self::_bps(cost, Self::_entry_rate(tokens));
+		let total = cost.checked_add(fee).ok_or("Overflow while buying tokens.")?;
+		Self::_to_balance(total)?;
+		ensure!(max_cost.map_or(true, |max| total <= max), "Cost exceeds the maximum.");
+		Self::balance_of(beneficiary).checked_add(tokens).ok_or("Overflow while minting new tokens.")?;
+		Self::reserve().checked_add(&cost_).ok_or("Overflow in reserve.")?;
+		Ok((cost, fee))
+	}
+
+	/// Internal sell function: burns `tokens` from `seller` and pays the curve return
+	/// to `payee`.
+	fn _sell(seller: T::AccountId, payee: T::AccountId, tokens: u128) -> Result {
+		ensure!(!Self::auction_open(), "The curve opens after the pre-sale.");
+		ensure!(!Self::paused(), "Trading is paused.");
+		ensure!(!Self::sells_paused(), "Sells are paused by the circuit breaker.");
+		ensure!(tokens >= T::MIN_TRADE, "Sell is below the minimum trade size.");
+		ensure!(Self::balance_of(&seller) >= tokens, "Not enough balance.");
+		ensure!(Self::sellable_balance(&seller) >= tokens, "Tokens are still locked after purchase.");
+		ensure!(Self::_cooled_down(&seller), "Account bought too recently to sell.");
+
+		let ret_amount = Self::return_on_sell(tokens).ok_or("Underflow while selling tokens.")?;
+		ensure!(Self::_price_allowed(TradeSide::Sell, tokens), "Sell would push the price below the floor.");
+		ensure!(Self::_ratio_allows_sell(tokens, ret_amount), "Sell would leave the reserve below the minimum ratio.");
+		let ret_amount_ = Self::_to_balance(ret_amount)?;
+		let fee = Self::_bps(ret_amount, Self::_exit_rate(Some(&seller), tokens));
+		let payout_ = Self::_to_balance(ret_amount - fee)?;
+		ensure!(Self::_can_receive(&payee, payout_), "Payout is below the existential deposit.");
+
+		// Burn before touching the reserve so the payout is the last step.
+		Self::_burn(seller.clone(), tokens)?;
+
+		let payout = PendingPayout { payee, ret_amount: ret_amount_, payout: payout_ };
+		if Self::_pays_now(ret_amount_) {
+			Self::_pay_out(payout);
+		} else {
+			// The reserve is deployed; the payout waits its turn for liquidity.
+			let tail = Self::payout_tail();
+			Self::deposit_event(RawEvent::PayoutQueued(payout.payee.clone(), payout_));
+			<PendingPayouts<T>>::insert(tail, payout);
+			<PayoutTail<T>>::put(tail + 1);
+		}
+
+		Self::_record_volume(ret_amount);
+		Self::_check_reserve_ratio();
+		Self::_record_sell(ret_amount);
+		let trade_id = Self::_next_trade_id();
+		Self::_record_trade(trade_id, &seller, TradeSide::Sell, tokens, ret_amount);
+		Self::deposit_event(RawEvent::Sell(trade_id, Some(seller), tokens, ret_amount, <timestamp::Module<T>>::now()));
+
+		Ok(())
+	}
+
+	/// Whether selling `tokens` for `ret_amount` leaves the reserve at or above the
+	/// minimum ratio to what sellers of the remaining supply are owed.
+	fn _ratio_allows_sell(tokens: u128, ret_amount: u128) -> bool {
+		let min_ratio = Self::min_reserve_ratio();
+		if min_ratio == 0 {
+			return true;
+		}
+		let reserve = Self::_total_reserve().saturating_sub(ret_amount);
+		let owed = match Self::curve_params().area(0, Self::_backed_supply().saturating_sub(tokens), true) {
+			Some(owed) => owed,
+			None => return false,
+		};
+		reserve.checked_mul(BPS).map_or(true, |reserve| reserve >= owed.saturating_mul(min_ratio as u128))
+	}
+
+	/// Whether trading is paused after a failed solvency check.
+	pub fn paused() -> bool {
+		Self::paused_at().is_some()
+	}
+
+	/// Whether the circuit breaker is holding sells back.
+	pub fn sells_paused() -> bool {
+		Self::sells_paused_until().map_or(false, |until| <system::Module<T>>::block_number() < until)
+	}
+
+	/// Counts `ret_amount` paid out for a sell against the circuit breaker's window,
+	/// tripping it once the window's sells exceed its share of the reserve they were
+	/// paid from.
+	fn _record_sell(ret_amount: u128) {
+		let breaker = match Self::circuit_breaker() {
+			Some(breaker) => breaker,
+			None => return,
+		};
+		let now = <system::Module<T>>::block_number();
+		let (mut start, mut sold) = Self::sell_window();
+		if now >= start + breaker.window {
+			start = now;
+			sold = 0;
+		}
+		sold = sold.saturating_add(ret_amount);
+		<SellWindow<T>>::put((start, sold));
+
+		let reserve = Self::_total_reserve().saturating_add(sold);
+		if sold > Self::_bps(reserve, breaker.max_share) {
+			let until = now + breaker.cooldown;
+			<SellsPausedUntil<T>>::put(until);
+			<SellWindow<T>>::put((until, 0));
+			Self::deposit_event(RawEvent::CircuitBroken(sold, reserve, until));
+		}
+	}
+
+	/// Whether a pre-sale auction is running, keeping the curve closed.
+	pub fn auction_open() -> bool {
+		Self::auction().map_or(false, |auction| auction.clearing_price.is_none())
+	}
+
+	/// Closes the auction at the current price, or at the committed currency per token
+	/// of the tranche if that is higher: issues the tokens sold on top of the existing
+	/// supply, seeds the reserve with the proceeds and raises the base price so the
+	/// curve opens at the clearing price.
+	fn _close_auction(mut auction: DutchAuction<T::BlockNumber>, now: T::BlockNumber) {
+		// Committed currency never buys more than the tranche: when the price has fallen
+		// below what was committed per token, the auction clears at the latter.
+		let committed_per_token = auction.committed / auction.tranche
+			+ if auction.committed % auction.tranche > 0 { 1 } else { 0 };
+		let price = cmp::max(auction.price_at(now), committed_per_token);
+		let sold = auction.sold_at(price);
+		auction.clearing_price = Some(price);
+
+		let supply = Self::total_supply().saturating_add(sold);
+		let curve = CurveParams { base_price: 0, ..Self::curve_params() };
+		<BasePrice<T>>::put(price.saturating_sub(curve.spot_price(supply)));
+		<AuctionUnclaimed<T>>::put(sold);
+		<Reserve<T>>::mutate(|reserve| *reserve += Self::_saturating_balance(auction.committed));
+		Self::_set_supply(supply);
+		<Auction<T>>::put(auction);
+
+		Self::deposit_event(RawEvent::AuctionClosed(price, sold));
+	}
+
+	/// Whether a sell returning `ret_amount` can be paid immediately: the liquid
+	/// reserve covers it and no earlier payout is waiting.
+	fn _pays_now(ret_amount: T::Balance) -> bool {
+		Self::payout_head() == Self::payout_tail() && Self::reserve() >= ret_amount
+	}
+
+	/// Takes a sell's return out of the reserve, keeps the exit tribute and pays the rest.
+	fn _pay_out(payout: PendingPayout<T::AccountId, T::Balance>) {
+		<Reserve<T>>::mutate(|reserve| *reserve -= payout.ret_amount);
+		Self::_collect_tribute(Self::_from_balance(payout.ret_amount - payout.payout));
+		<balances::Module<T>>::increase_free_balance_creating(&payout.payee, payout.payout);
+	}
+
+	/// Pays queued sell payouts in order for as long as the liquid reserve covers them.
+	fn _honor_payouts() {
+		let (mut head, tail) = (Self::payout_head(), Self::payout_tail());
+		while head < tail {
+			let payout = match Self::pending_payout(head) {
+				Some(payout) => payout,
+				None => break,
+			};
+			if Self::reserve() < payout.ret_amount {
+				break;
+			}
+
+			<PendingPayouts<T>>::remove(head);
+			Self::deposit_event(RawEvent::PayoutPaid(payout.payee.clone(), payout.payout));
+			Self::_pay_out(payout);
+			head += 1;
+		}
+		<PayoutHead<T>>::put(head);
+	}
+
+	/// Currency paid out for `seller` selling `tokens`: the curve return less the exit
+	/// tribute.
+	fn _sell_payout(seller: Option<&T::AccountId>, tokens: u128) -> Option<u128> {
+		let ret_amount = Self::return_on_sell(tokens)?;
+		Some(ret_amount - Self::_bps(ret_amount, Self::_exit_rate(seller, tokens)))
+	}
+
+	/// Internal mint function for ERC20 token.
+	fn _mint(to: T::AccountId, amount: u128) -> Result {
+		let balance = Self::balance_of(&to);
+
+		let new_balance = match balance.checked_add(amount) {
+			Some(x) => x,
+			None => return Err("Overflow while minting new tokens."),
+		};
+
+		let supply = Self::total_supply();
+		
+		let new_supply = match supply.checked_add(amount) {
+			Some(x) => x,
+			None => return Err("Overflow while minting new tokens."),
+		};
+		ensure!(Self::_within_max_supply(new_supply), "Minting would exceed the maximum supply.");
+
+		Self::_set_supply(new_supply);
+		Self::_set_balance(&to, new_balance);
+
+		Self::deposit_event(RawEvent::Transfer(None, Some(to), amount));
+		Ok(())
+	}
+
+	/// Mints `amount` to `to` outside the curve, moving their curve cost from the
+	/// funding pool into the reserve so the new supply stays backed.
+	fn _mint_backed(to: T::AccountId, amount: u128) -> Result {
+		let cost = Self::cost_to_buy(amount).ok_or("Overflow while minting new tokens.")?;
+		let cost_ = Self::_to_balance(cost)?;
+		ensure!(Self::funding_pool() >= cost_, "Funding pool cannot back the minted tokens.");
+		Self::reserve().checked_add(&cost_).ok_or("Overflow in reserve.")?;
+
+		Self::_mint(to, amount)?;
+		<FundingPool<T>>::mutate(|pool| *pool -= cost_);
+		<Reserve<T>>::mutate(|reserve| *reserve += cost_);
+		Ok(())
+	}
+
+	/// Burns `amount` from `from` outside the curve, returning the reserve behind them
+	/// to the funding pool.
+	fn _burn_backed(from: T::AccountId, amount: u128) -> Result {
+		let ret_amount = Self::return_on_sell(amount).ok_or("Underflow while burning tokens.")?;
+		let ret_amount_ = Self::_to_balance(ret_amount)?;
+		ensure!(Self::reserve() >= ret_amount_, "Liquid reserve cannot cover the burn.");
+
+		Self::_burn(from, amount)?;
+		<Reserve<T>>::mutate(|reserve| *reserve -= ret_amount_);
+		<FundingPool<T>>::mutate(|pool| *pool += ret_amount_);
+		Ok(())
+	}
+
+	/// Internal burn function for Erc20 token.
+	fn _burn(from: T::AccountId, amount: u128) -> Result {
+		let balance = Self::balance_of(&from);
+
+		let new_balance = match balance.checked_sub(amount) {
+			Some(x) => x,
+			None => return Err("Underflow while burning tokens."),
+		};
+
+		let supply = Self::total_supply();
+
+		let new_supply = match supply.checked_sub(amount) {
+			Some(x) => x,
+			None => return Err("Underflow while burning tokens."),
+		};
+
+		Self::_set_supply(new_supply);
+		Self::_set_balance(&from, new_balance);
+
+		Self::deposit_event(RawEvent::Transfer(Some(from), None, amount));
+		Ok(())
+	}
+
+	/// Nets queued buys against queued sells at the current spot price, then routes
+	/// whatever is left on either side through the curve. Orders whose limit cannot be
+	/// met keep resting in the queues. Returns the number of fills made.
+	fn _settle_orders() -> u32 {
+		if Self::auction_open() || Self::paused() {
+			return 0;
+		}
+		let mut buys = <BuyQueue<T>>::take();
+		let mut sells = <SellQueue<T>>::take();
+		if buys.is_empty() && sells.is_empty() {
+			return 0;
+		}
+		let mut filled = 0;
+
+		let price = Self::price_at(Self::total_supply());
+		let (mut b, mut s) = (0, 0);
+		while b < buys.len() && s < sells.len() {
+			if buys[b].limit < price {
+				b += 1;
+				continue;
+			}
+			if sells[s].limit > price {
+				s += 1;
+				continue;
+			}
+
+			let fill = cmp::min(buys[b].tokens, sells[s].tokens);
+			let value = match fill.checked_mul(price) {
+				Some(x) if x <= buys[b].escrow => x,
+				_ => {
+					b += 1;
+					continue;
+				}
+			};
+			if !Self::_can_receive(&sells[s].who, Self::_saturating_balance(value)) {
+				s += 1;
+				continue;
+			}
+			if !Self::_within_purchase_cap(&buys[b].who, fill) {
+				b += 1;
+				continue;
+			}
+			if !Self::_cooled_down(&sells[s].who) {
+				s += 1;
+				continue;
+			}
+
+			Self::_fill_match(&mut buys[b], &mut sells[s], fill, value);
+			filled += 1;
+
+			if buys[b].tokens == 0 {
+				b += 1;
+			}
+			if sells[s].tokens == 0 {
+				s += 1;
+			}
+		}
+
+		// Each curve settlement searches for the largest fill, so only so many orders
+		// are tried per settlement. Orders not tried move to the front of the queue.
+		let mut untried_buys = Vec::new();
+		let mut resting_buys = Vec::new();
+		for (i, order) in buys.into_iter().enumerate() {
+			if i >= MAX_CURVE_SETTLEMENTS {
+				untried_buys.push(order);
+				continue;
+			}
+			let (curve_filled, resting) = Self::_settle_buy_on_curve(order);
+			filled += curve_filled as u32;
+			resting_buys.extend(resting);
+		}
+		let mut untried_sells = Vec::new();
+		let mut resting_sells = Vec::new();
+		for (i, order) in sells.into_iter().enumerate() {
+			if i >= MAX_CURVE_SETTLEMENTS {
+				untried_sells.push(order);
+				continue;
+			}
+			let (curve_filled, resting) = Self::_settle_sell_on_curve(order);
+			filled += curve_filled as u32;
+			resting_sells.extend(resting);
+		}
+
+		for order in resting_buys.iter_mut().chain(resting_sells.iter_mut()) {
+			order.resting = true;
+		}
+		untried_buys.extend(resting_buys);
+		untried_sells.extend(resting_sells);
+		<BuyQueue<T>>::put(untried_buys);
+		<SellQueue<T>>::put(untried_sells);
+		filled
+	}
+
+	/// Moves `fill` held tokens from the seller to the buyer and `value` held currency
+	/// from the buyer to the seller. The total supply and the reserve are untouched.
+	///
+	/// When exactly one side is a resting order, it earns the maker rebate. The taker
+	/// pays it out of the room its limit leaves beyond the clearing price, so the
+	/// rebate never takes the taker past its limit.
+	fn _fill_match(buy: &mut Order<T::AccountId>, sell: &mut Order<T::AccountId>, fill: u128, value: u128) {
+		let price = value / fill;
+		let rebate = Self::_bps(value, Self::maker_rebate_rate());
+		let (maker, rebate, paid_by_buyer) = match (buy.resting, sell.resting) {
+			(true, false) => {
+				let room = fill.saturating_mul(price - sell.limit);
+				(Some(buy.who.clone()), cmp::min(rebate, room), 0)
+			},
+			(false, true) => {
+				let room = cmp::min(fill.saturating_mul(buy.limit - price), buy.escrow - value);
+				let rebate = cmp::min(rebate, room);
+				(Some(sell.who.clone()), rebate, rebate)
+			},
+			_ => (None, 0, 0),
+		};
+		// A resting seller is paid in full; a taking seller gives up the rebate.
+		let proceeds = value - (rebate - paid_by_buyer);
+
+		buy.tokens -= fill;
+		buy.escrow -= value + paid_by_buyer;
+		sell.tokens -= fill;
+
+		Self::_set_balance(&buy.who, Self::balance_of(&buy.who) + fill);
+		<balances::Module<T>>::increase_free_balance_creating(&sell.who, Self::_saturating_balance(proceeds));
+		if let Some(maker) = maker.filter(|_| rebate > 0) {
+			<MakerRebates<T>>::mutate(&maker, |earned| *earned = earned.saturating_add(rebate));
+			Self::deposit_event(RawEvent::MakerRebateEarned(maker, rebate));
+		}
+
+		Self::deposit_event(RawEvent::Transfer(Some(sell.who.clone()), Some(buy.who.clone()), fill));
+		Self::_record_volume(value);
+		Self::_check_reserve_ratio();
+		let trade_id = Self::_next_trade_id();
+		Self::_record_match(trade_id, &buy.who, &sell.who, fill, value);
+		Self::deposit_event(RawEvent::OrdersMatched(trade_id, buy.who.clone(), sell.who.clone(), fill, value));
+	}
+
+	/// Buys as much of the unmatched remainder of a buy order from the curve as its
+	/// limit, its escrow and the price ceiling allow. Whatever is left keeps resting
+	/// with its escrow; a fully filled order has the rest of its escrow returned.
+	/// Reports whether the curve filled anything and hands back the resting order.
+	fn _settle_buy_on_curve(mut order: Order<T::AccountId>) -> (bool, Option<Order<T::AccountId>>) {
+		let mut filled = false;
+		if order.tokens > 0 {
+			let quote = |tokens: u128| Self::cost_to_buy(tokens)
+				.and_then(|cost| Some((cost, cost.checked_add(Self::_bps(cost, Self::_entry_rate(tokens)))?)))
+				.filter(|(_, total)| *total <= order.escrow && tokens.checked_mul(order.limit).map_or(false, |max| *total <= max))
+				.filter(|_| Self::_price_allowed(TradeSide::Buy, tokens))
+				.filter(|_| Self::_within_purchase_cap(&order.who, tokens));
+			let fill = bonded_curve::max_tokens(|tokens| tokens <= order.tokens && quote(tokens).is_some());
+			let (cost, total) = match quote(fill).filter(|_| fill > 0 && fill >= T::MIN_TRADE) {
+				Some(quote) => quote,
+				None => return (false, Some(order)),
+			};
+
+			let supply = Self::total_supply();
+			Self::_set_supply(supply + fill);
+			Self::_set_balance(&order.who, Self::balance_of(&order.who) + fill);
+			<Reserve<T>>::mutate(|reserve| *reserve += Self::_saturating_balance(cost));
+			Self::_collect_entry_fee(&order.who, total - cost, None);
+			order.escrow -= total;
+			order.tokens -= fill;
+
+			Self::deposit_event(RawEvent::Transfer(None, Some(order.who.clone()), fill));
+			Self::_record_volume(cost);
+			Self::_check_reserve_ratio();
+			let trade_id = Self::_next_trade_id();
+			Self::_record_trade(trade_id, &order.who, TradeSide::Buy, fill, cost);
+			Self::deposit_event(RawEvent::Buy(trade_id, Some(order.who.clone()), fill, cost, <timestamp::Module<T>>::now()));
+			filled = true;
+
+			if order.tokens > 0 {
+				return (filled, Some(order));
+			}
+		}
+
+		if order.escrow > 0 {
+			<balances::Module<T>>::increase_free_balance_creating(&order.who, Self::_saturating_balance(order.escrow));
+		}
+		(filled, None)
+	}
+
+	/// Sells as much of the unmatched remainder of a sell order to the curve as its
+	/// limit, the liquid reserve and the price floor allow. Reports whether the curve
+	/// filled anything and hands back the order if tokens are left resting.
+	fn _settle_sell_on_curve(mut order: Order<T::AccountId>) -> (bool, Option<Order<T::AccountId>>) {
+		if order.tokens == 0 {
+			return (false, None);
+		}
+		if !Self::_cooled_down(&order.who) || Self::sells_paused() {
+			return (false, Some(order));
+		}
+
+		let quote = |tokens: u128| Self::return_on_sell(tokens)
+			.map(|ret| (ret, ret - Self::_bps(ret, Self::_exit_rate(Some(&order.who), tokens))))
+			.filter(|(_, payout)| tokens.checked_mul(order.limit).map_or(false, |min| *payout >= min))
+			.filter(|(ret, _)| Self::reserve() >= Self::_saturating_balance(ret))
+			.filter(|_| Self::_price_allowed(TradeSide::Sell, tokens))
+			.filter(|(ret, _)| Self::_ratio_allows_sell(tokens, *ret));
+		let fill = bonded_curve::max_tokens(|tokens| tokens <= order.tokens && quote(tokens).is_some());
+		let (ret_amount, payout) = match quote(fill)
+			.filter(|_| fill > 0 && fill >= T::MIN_TRADE)
+			.filter(|(_, payout)| Self::_can_receive(&order.who, Self::_saturating_balance(payout)))
+		{
+			Some(quote) => quote,
+			None => return (false, Some(order)),
+		};
+
+		let ret_amount_ = Self::_saturating_balance(ret_amount);
+		Self::_set_supply(Self::total_supply() - fill);
+		<Reserve<T>>::mutate(|reserve| *reserve -= ret_amount_);
+		Self::_collect_tribute(ret_amount - payout);
+		<balances::Module<T>>::increase_free_balance_creating(&order.who, Self::_saturating_balance(payout));
+		order.tokens -= fill;
+
+		Self::deposit_event(RawEvent::Transfer(Some(order.who.clone()), None, fill));
+		Self::_record_volume(ret_amount);
+		Self::_check_reserve_ratio();
+		Self::_record_sell(ret_amount);
+		let trade_id = Self::_next_trade_id();
+		Self::_record_trade(trade_id, &order.who, TradeSide::Sell, fill, ret_amount);
+		Self::deposit_event(RawEvent::Sell(trade_id, Some(order.who.clone()), fill, ret_amount, <timestamp::Module<T>>::now()));
+
+		(true, if order.tokens > 0 { Some(order) } else { None })
+	}
+
+	/// Makes one purchase of a recurring buy and reschedules it, refunding what is left
+	/// of the escrow after the last period.
+	fn _execute_recurring_buy(id: u64, now: T::BlockNumber) {
+		let mut plan = match Self::recurring_buy(id) {
+			Some(plan) => plan,
+			None => return,
+		};
+
+		let spend = cmp::min(plan.amount, plan.escrow);
+		let tokens = Self::_tokens_for_spend(spend);
+		let tokens = match Self::purchase_cap() {
+			Some(cap) => cmp::min(tokens, cap.saturating_sub(Self::purchased(&plan.who))),
+			None => tokens,
+		};
+		// Priced before minting, like `buy`, so the purchase is not counted in the supply
+		// twice.
+		let quote = Self::cost_to_buy(tokens)
+			.filter(|_| Self::_recurring_buy_allowed(tokens))
+			.map(|cost| (cost, Self::_bps(cost, Self::_entry_rate(tokens))))
+			.filter(|(cost, fee)| cost.saturating_add(*fee) <= plan.escrow);
+		if let Some((cost, fee)) = quote {
+			if Self::_mint(plan.who.clone(), tokens).is_ok() {
+				<Reserve<T>>::mutate(|reserve| *reserve += Self::_saturating_balance(cost));
+				Self::_collect_entry_fee(&plan.who, fee, None);
+				plan.escrow -= cost + fee;
+
+				Self::_record_volume(cost);
+				Self::_check_reserve_ratio();
+				let trade_id = Self::_next_trade_id();
+				Self::_record_trade(trade_id, &plan.who, TradeSide::Buy, tokens, cost);
+				// Recurring buys run before the timestamp inherent, so this carries the
+				// previous block's timestamp.
+				Self::deposit_event(RawEvent::Buy(trade_id, Some(plan.who.clone()), tokens, cost, <timestamp::Module<T>>::now()));
+				Self::deposit_event(RawEvent::RecurringBuyExecuted(id, plan.who.clone(), tokens, cost));
+			}
+		}
+
+		plan.remaining -= 1;
+		// A plan that finds no room to fall due again ends early.
+		match Self::_recurring_slot(now + plan.interval).filter(|_| plan.remaining > 0) {
+			Some(due) => {
+				<RecurringBuysDue<T>>::mutate(due, |ids| ids.push(id));
+				<RecurringBuys<T>>::insert(id, plan);
+			}
+			None => {
+				<RecurringBuys<T>>::remove(id);
+				if plan.escrow > 0 {
+					<balances::Module<T>>::increase_free_balance_creating(&plan.who, Self::_saturating_balance(plan.escrow));
+				}
+			}
+		}
+	}
+
+	/// Whether a recurring buy of `tokens` may run now, under the same checks as `buy`.
+	fn _recurring_buy_allowed(tokens: u128) -> bool {
+		tokens > 0
+			&& tokens >= T::MIN_TRADE
+			&& !Self::paused()
+			&& !Self::auction_open()
+			&& Self::_price_allowed(TradeSide::Buy, tokens)
+	}
+
+	/// First block from `from` with room for another recurring buy to fall due, looking
+	/// at most `MAX_RECURRING_SLOT_SEARCH` blocks ahead.
+	fn _recurring_slot(from: T::BlockNumber) -> Option<T::BlockNumber> {
+		let mut at = from;
+		for _ in 0..MAX_RECURRING_SLOT_SEARCH {
+			if Self::recurring_buys_due(at).len() < MAX_RECURRING_DUE {
+				return Some(at);
+			}
+			at = at + As::sa(1);
+		}
+		None
+	}
+
+	/// Adds traded currency to the bucket of the current block.
+	fn _record_volume(value: u128) {
+		let now: u64 = <system::Module<T>>::block_number().as_();
+		let bucket = now / VOLUME_BUCKET_BLOCKS;
+		<BucketVolume<T>>::mutate(bucket, |volume| *volume = volume.saturating_add(value));
+	}
+
+	/// Hands out the next sequential trade identifier. Wraps rather than failing a
+	/// trade after 2^64 of them.
+	fn _next_trade_id() -> u64 {
+		let id = Self::trade_count();
+		<TradeCount<T>>::put(id.wrapping_add(1));
+		id
+	}
+
+	/// Records a trade with the curve in the candles and the history ring buffer and
+	/// notifies the configured trade handlers.
+	fn _record_trade(id: u64, who: &T::AccountId, side: TradeSide, tokens: u128, value: u128) {
+		Self::_account_trade(who, side, tokens, value);
+		Self::_log_trade(id, who, None, side, tokens, value);
+	}
+
+	/// Records a fill between two orders, accounting it to both sides but adding it to
+	/// the candles and the history once.
+	fn _record_match(id: u64, buyer: &T::AccountId, seller: &T::AccountId, tokens: u128, value: u128) {
+		Self::_account_trade(buyer, TradeSide::Buy, tokens, value);
+		Self::_account_trade(seller, TradeSide::Sell, tokens, value);
+		Self::_log_trade(id, buyer, Some(seller), TradeSide::Buy, tokens, value);
+	}
+
+	/// Updates the volume, cost basis and locks of one side of a trade.
+	fn _account_trade(who: &T::AccountId, side: TradeSide, tokens: u128, value: u128) {
+		match side {
+			TradeSide::Buy => {
+				let now = <system::Module<T>>::block_number();
+				Self::_mutate_account(who, |account| {
+					account.purchased = account.purchased.saturating_add(tokens);
+					account.last_buy = Some(now);
+				});
+				Self::_lock_bought(who, tokens);
+				<CumulativeBuyVolume<T>>::mutate(|volume| *volume = volume.saturating_add(value));
+				if T::TRACK_COST_BASIS {
+					Self::_mutate_account(who, |account| account.total_spent = account.total_spent.saturating_add(value));
+				}
+				T::OnBuy::on_trade(who, tokens, value)
+			}
+			TradeSide::Sell => {
+				<CumulativeSellVolume<T>>::mutate(|volume| *volume = volume.saturating_add(value));
+				if T::TRACK_COST_BASIS {
+					Self::_mutate_account(who, |account| account.total_proceeds = account.total_proceeds.saturating_add(value));
+				}
+				T::OnSell::on_trade(who, tokens, value)
+			}
+		}
+	}
+
+	/// Adds a trade to the current candle and the trade history.
+	fn _log_trade(id: u64, who: &T::AccountId, counterparty: Option<&T::AccountId>, side: TradeSide, tokens: u128, value: u128) {
+		Self::_record_candle(tokens, value);
+
+		let size = Self::trade_history_size();
+		if size == 0 {
+			return;
+		}
+
+		let slot = Self::trade_history_cursor() % size;
+		<TradeHistory<T>>::insert(slot, TradeRecord {
+			id,
+			account: T::Hashing::hash_of(who),
+			counterparty: counterparty.map(|who| T::Hashing::hash_of(who)),
+			side,
+			tokens,
+			price: value.checked_div(tokens).unwrap_or(0),
+			block: <system::Module<T>>::block_number(),
+			timestamp: <timestamp::Module<T>>::now(),
+		});
+		<TradeHistoryCursor<T>>::put((slot + 1) % size);
+	}
+
+	/// Adds a trade to the candle of the current window. Opening a new window drops the
+	/// candles that fall out of retention.
+	fn _record_candle(tokens: u128, value: u128) {
+		let length = Self::candle_length();
+		if length.is_zero() || tokens == 0 {
+			return;
+		}
+		let price = value / tokens;
+		let window: u64 = (<system::Module<T>>::block_number() / length).as_();
+
+		let candle = match Self::candle(window) {
+			Some(candle) => Candle {
+				high: cmp::max(candle.high, price),
+				low: cmp::min(candle.low, price),
+				close: price,
+				volume: candle.volume.saturating_add(value),
+				..candle
+			},
+			None => {
+				Self::_prune_candles(window.saturating_sub(Self::candle_retention()));
+				<LatestCandle<T>>::put(window);
+				Candle { open: price, high: price, low: price, close: price, volume: value }
+			},
+		};
+		<Candles<T>>::insert(window, candle);
+	}
+
+	/// Removes the candles of windows before `before`, returning how many were removed.
+	/// Candles are only kept for the retention before the latest window, so at most
+	/// that many windows are looked at.
+	fn _prune_candles(before: u64) -> u32 {
+		let latest = Self::latest_candle();
+		let mut pruned = 0;
+		for window in latest.saturating_sub(Self::candle_retention())..cmp::min(before, latest + 1) {
+			if <Candles<T>>::exists(window) {
+				<Candles<T>>::remove(window);
+				pruned += 1;
+			}
+		}
+		pruned
+	}
+
+	/// Stores a fresh statistics snapshot and drops the bucket that fell out of the window.
+	fn _publish_stats(now: T::BlockNumber) {
+		let block: u64 = now.as_();
+		let bucket = block / VOLUME_BUCKET_BLOCKS;
+		let first = bucket.saturating_sub(VOLUME_WINDOW_BUCKETS - 1);
+		let volume_24h = (first..=bucket)
+			.fold(0u128, |total, b| total.saturating_add(Self::bucket_volume(b)));
+		if first > 0 {
+			<BucketVolume<T>>::remove(first - 1);
+		}
+
+		let supply = Self::total_supply();
+		<Stats<T>>::put(CurveStats {
+			block: now,
+			supply,
+			reserve: Self::reserve(),
+			spot_price: Self::price_at(supply),
+			volume_24h,
+		});
+	}
+
+	/// Adds this block's spot price to the cumulative price, closes the averaging
+	/// window once it is complete, and pushes both prices to the price feed.
+	fn _feed_price(now: T::BlockNumber) {
+		let spot_price = Self::spot_price();
+		let cumulative = Self::price_cumulative().saturating_add(spot_price);
+		<PriceCumulative<T>>::put(cumulative);
+
+		let window = Self::twap_window();
+		let (start, start_cumulative) = Self::twap_start();
+		if !window.is_zero() && now >= start + window {
+			let blocks: u64 = (now - start).as_();
+			<Twap<T>>::put((cumulative - start_cumulative) / blocks as u128);
+			<TwapStart<T>>::put((now, cumulative));
+		}
+
+		T::PriceFeed::on_price(spot_price, Self::twap(), now);
+	}
+
+	/// Reports the reserve against what sellers of the backed supply are owed. The
+	/// ratio saturates when nothing is owed.
+	fn _report_solvency() {
+		let reserve = Self::_total_reserve();
+		let owed = Self::curve_params().area(0, Self::_backed_supply(), true).unwrap_or(u128::max_value());
+		Self::deposit_event(RawEvent::SolvencyReport(reserve, owed, Self::_ratio(reserve, owed)));
+	}
+
+	/// Ratio of `reserve` to `owed` in basis points, saturating when nothing is owed.
+	fn _ratio(reserve: u128, owed: u128) -> u32 {
+		match owed {
+			0 => u32::max_value(),
+			owed => cmp::min(reserve.saturating_mul(BPS) / owed, u32::max_value() as u128) as u32,
+		}
+	}
+
+	/// Emits `ReserveRatioLow` when the reserve ratio has fallen below an alert
+	/// threshold since the last trade.
+	fn _check_reserve_ratio() {
+		let thresholds = Self::ratio_alerts();
+		if thresholds.is_empty() {
+			return;
+		}
+		let owed = Self::curve_params().area(0, Self::_backed_supply(), true).unwrap_or(u128::max_value());
+		let ratio = Self::_ratio(Self::_total_reserve(), owed);
+		let last = Self::last_reserve_ratio().unwrap_or(u32::max_value());
+		if thresholds.iter().any(|threshold| ratio < *threshold && *threshold <= last) {
+			Self::deposit_event(RawEvent::ReserveRatioLow(ratio));
+		}
+		<LastReserveRatio<T>>::put(ratio);
+	}
+
+	/// Largest number of tokens whose curve cost plus entry tribute at the current
+	/// supply fits in `spend`.
+	fn _tokens_for_spend(spend: u128) -> u128 {
+		let fits = |tokens: u128| Self::cost_to_buy(tokens)
+			.and_then(|cost| cost.checked_add(Self::_bps(cost, Self::_entry_rate(tokens))))
+			.map_or(false, |total| total <= spend);
+		bonded_curve::max_tokens(fits)
+	}
+
+	/// Entry tribute rate for buying `tokens`, including any large-trade surcharge.
+	fn _entry_rate(tokens: u128) -> u32 {
+		let supply_after = Self::total_supply().saturating_add(tokens);
+		Self::_tribute_rate(Self::entry_fee(), tokens, supply_after)
+	}
+
+	/// Exit tribute rate for `seller` selling `tokens`, lowered by how long the seller
+	/// has held tokens and including any large-trade surcharge. Without a seller, the
+	/// base rate applies.
+	fn _exit_rate(seller: Option<&T::AccountId>, tokens: u128) -> u32 {
+		let base = match seller {
+			Some(seller) => cmp::min(Self::exit_fee(), Self::_loyalty_rate(seller)),
+			None => Self::exit_fee(),
+		};
+		Self::_tribute_rate(base, tokens, Self::total_supply())
+	}
+
+	/// Average acquisition block of `balance` tokens after topping up `old` tokens held
+	/// since `since` at `now`. Rounded towards `now`, so a top-up never makes a holding
+	/// look older than it is. Untracked holdings count as acquired `now`.
+	fn _entry_block(since: Option<T::BlockNumber>, old: u128, balance: u128, now: T::BlockNumber) -> T::BlockNumber {
+		let since = match since {
+			Some(since) if old > 0 => since,
+			_ => return now,
+		};
+		let elapsed: u64 = (now - since).as_();
+		let added = balance - old;
+		let shift = match (elapsed as u128).checked_mul(added) {
+			Some(product) => product / balance + if product % balance > 0 { 1 } else { 0 },
+			None => elapsed as u128,
+		};
+		since + T::BlockNumber::sa(shift as u64)
+	}
+
+	/// Lowest exit tribute rate the holding duration of `who` qualifies for. Holders
+	/// from before holding was tracked qualify for none.
+	fn _loyalty_rate(who: &T::AccountId) -> u32 {
+		let since = match Self::account(who).held_since {
+			Some(since) => since,
+			None => return u32::max_value(),
+		};
+		let held = <system::Module<T>>::block_number() - since;
+		Self::loyalty_tiers().into_iter()
+			.filter(|(min_held, _)| held >= *min_held)
+			.map(|(_, rate)| rate)
+			.min()
+			.unwrap_or(u32::max_value())
+	}
+
+	/// `base` plus the surcharge of the highest tier the trade's share of `supply`
+	/// reaches, capped at 100%. Zero while fees are switched off.
+	fn _tribute_rate(base: u32, tokens: u128, supply: u128) -> u32 {
+		if !Self::fees_enabled() {
+			return 0;
+		}
+		let share = if supply == 0 {
+			BPS
+		} else {
+			cmp::min(tokens.saturating_mul(BPS) / supply, BPS)
+		};
+		let surcharge = Self::fee_tiers().into_iter()
+			.filter(|(threshold, _)| share >= *threshold as u128)
+			.map(|(_, surcharge)| surcharge)
+			.max()
+			.unwrap_or(0);
+		cmp::min(base.saturating_add(surcharge) as u128, BPS) as u32
+	}
+
+	/// Royalty owed to the creator when `from` transfers `value`. The creator's own
+	/// transfers are exempt.
+	fn _royalty(from: &T::AccountId, value: u128) -> u128 {
+		match Self::creator() {
+			Some(ref creator) if creator != from => Self::_bps(value, Self::transfer_royalty()),
+			_ => 0,
+		}
+	}
+
+	/// Pays the referrer's share of an entry tribute and splits the rest between the
+	/// beneficiaries.
+	fn _collect_entry_fee(buyer: &T::AccountId, fee: u128, referrer: Option<T::AccountId>) {
+		let mut tribute = fee;
+		if let Some(referrer) = referrer.filter(|r| r != buyer) {
+			let reward = Self::_bps(fee, Self::referral_reward());
+			if reward > 0 {
+				tribute -= reward;
+				<ReferralRewards<T>>::mutate(&referrer, |owed| *owed = owed.saturating_add(reward));
+				<ReferralEarnings<T>>::mutate(&referrer, |earned| *earned = earned.saturating_add(reward));
+				Self::deposit_event(RawEvent::ReferralRewarded(referrer, buyer.clone(), reward));
+			}
+		}
+		Self::_collect_tribute(tribute);
+	}
+
+	/// Sets aside the insurance and creator shares of a tribute and allots the rest to
+	/// the beneficiaries by weight, to be claimed. Whatever rounds away, or all of it if there are no
+	/// beneficiaries, goes to the fee handler.
+	fn _collect_tribute(tribute: u128) {
+		let insured = Self::_bps(tribute, Self::insurance_share());
+		<InsuranceFund<T>>::mutate(|fund| *fund += Self::_saturating_balance(insured));
+		let owed = if Self::creator().is_some() { Self::_bps(tribute, Self::creator_share()) } else { 0 };
+		<CreatorFees<T>>::mutate(|fees| *fees += Self::_saturating_balance(owed));
+		let tribute = tribute - insured - owed;
+
+		let beneficiaries = Self::beneficiaries();
+		let total_weight = beneficiaries.iter().fold(0u128, |total, (_, weight)| total + *weight as u128);
+		let mut remainder = tribute;
+		for (who, weight) in beneficiaries {
+			let share = tribute.checked_mul(weight as u128)
+				.map_or_else(|| tribute / total_weight * weight as u128, |product| product / total_weight);
+			if share > 0 {
+				remainder -= share;
+				<BeneficiaryShares<T>>::mutate(&who, |owed| *owed = owed.saturating_add(share));
+			}
+		}
+		if remainder > 0 {
+			T::FeeHandler::on_unbalanced(Self::_saturating_balance(remainder));
+		}
+	}
+
+	/// Checks that `origin` may change a parameter directly. While there is an update
+	/// delay, parameters only change through updates applied from the queue.
+	fn _ensure_admin(origin: T::Origin) -> Result {
+		ensure!(
+			Self::update_delay().is_zero() || Self::applying_update(),
+			"Parameter updates must go through the update queue."
+		);
+		Self::_ensure_governor(origin)
+	}
+
+	/// Checks that `origin` may govern the token: root or the admin, unless governance
+	/// was renounced.
+	fn _ensure_governor(origin: T::Origin) -> Result {
+		Self::_governor(origin).map(|_| ())
+	}
+
+	/// Checks that `origin` may govern the token, returning the admin for a signed
+	/// origin and `None` for root.
+	fn _governor(origin: T::Origin) -> rstd::result::Result<Option<T::AccountId>, &'static str> {
+		ensure!(!Self::admin_renounced(), "Token governance has been renounced.");
+		let origin: Option<system::RawOrigin<T::AccountId>> = origin.into();
+		match origin {
+			Some(system::RawOrigin::Root) => Ok(None),
+			Some(system::RawOrigin::Signed(who)) => {
+				ensure!(Self::admin().as_ref() == Some(&who), "Origin must be root or the admin.");
+				Ok(Some(who))
+			}
+			_ => Err("Origin must be root or the admin."),
+		}
+	}
+
+	/// The category a parameter update falls into.
+	pub fn update_category(call: &Call<T>) -> UpdateCategory {
+		match call {
+			Call::set_entry_fee(..) | Call::set_exit_fee(..) | Call::set_fee_tiers(..) | Call::set_loyalty_tiers(..) |
+			Call::set_fees_enabled(..) | Call::set_transfer_royalty(..) | Call::set_beneficiaries(..) |
+			Call::set_keeper_incentive(..) | Call::set_insurance_share(..) | Call::set_creator_share(..) |
+			Call::set_maker_rebate_rate(..) | Call::set_flash_mint_config(..) => UpdateCategory::Fees,
+			Call::set_curve_schedule(..) | Call::start_launch(..) | Call::set_price_bounds(..) |
+			Call::set_inflation(..) | Call::set_rebalance_config(..) | Call::set_slope_controller(..) |
+			Call::set_circuit_breaker(..) => UpdateCategory::Curve,
+			Call::transfer_admin(..) | Call::renounce_admin(..) | Call::set_update_delay(..) |
+			Call::set_veto_threshold(..) | Call::set_tally_mode(..) | Call::set_vote_lock_era(..) => UpdateCategory::Governance,
+			_ => UpdateCategory::Other,
+		}
+	}
+
+	/// Applies a queued parameter update with root authority, unless it was cancelled
+	/// or vetoed. An update that fails leaves the parameters unchanged.
+	fn _apply_update(id: u64) {
+		let (call, queuer) = match <PendingUpdates<T>>::take(id) {
+			Some((_, call, queuer)) => (call, queuer),
+			None => return,
+		};
+		let tally = <VetoTally<T>>::take(id);
+		let mode = <UpdateTallyMode<T>>::take(id);
+		let threshold = Self::veto_threshold();
+		// Under a quadratic tally the threshold is a share of the supply's votes had
+		// one account held all of it.
+		if threshold > 0 && tally > Self::_bps(mode.weight(Self::total_supply()), threshold) {
+			Self::deposit_event(RawEvent::UpdateVetoed(id, tally));
+			return;
+		}
+		<ApplyingUpdate<T>>::put(true);
+		let origin = queuer.map_or(system::RawOrigin::Root, system::RawOrigin::Signed);
+		let applied = call.dispatch(origin.into()).is_ok();
+		<ApplyingUpdate<T>>::kill();
+		Self::deposit_event(RawEvent::UpdateApplied(id, applied));
+	}
+
+	/// Checks that `amount` can be taken from the free balance of `who` without
+	/// breaking the runtime's liquidity restrictions.
+	fn _ensure_can_withdraw(who: &T::AccountId, amount: T::Balance) -> Result {
+		<T as balances::Trait>::EnsureAccountLiquid::ensure_account_liquid(who)?;
+		ensure!(<balances::Module<T>>::free_balance(who) >= amount, "Not enough funds.");
+		Ok(())
+	}
+
+	/// Converts a reserve amount to the balance type, failing if it does not fit.
+	fn _to_balance(value: u128) -> rstd::result::Result<T::Balance, &'static str> {
+		<T::BalanceConversion as TryConvert<u128, T::Balance>>::try_convert(value)
+			.ok_or("Amount does not fit the balance type.")
+	}
+
+	/// Converts a reserve amount to the balance type, capping it at the largest balance.
+	/// Only for amounts that were taken in as balances and so always fit.
+	fn _saturating_balance(value: u128) -> T::Balance {
+		<T::BalanceConversion as TryConvert<u128, T::Balance>>::try_convert(value)
+			.unwrap_or_else(T::Balance::max_value)
+	}
+
+	/// Converts a balance to a reserve amount, capping it at `u128::max_value()`.
+	fn _from_balance(balance: T::Balance) -> u128 {
+		<T::BalanceConversion as TryConvert<T::Balance, u128>>::try_convert(balance)
+			.unwrap_or_else(u128::max_value)
+	}
+
+	/// Takes `amount` from the free balance of `who`, honouring the runtime's liquidity
+	/// restrictions. Reserved balance is never touched.
+	fn _withdraw(who: &T::AccountId, amount: T::Balance) -> Result {
+		Self::_ensure_can_withdraw(who, amount)?;
+		<balances::Module<T>>::decrease_free_balance(who, amount)
+	}
+
+	/// Whether paying `amount` to `who` leaves a live account. A payout below the
+	/// existential deposit to an account that does not exist would be reaped straight
+	/// away, so such payouts are refused instead of silently lost.
+	fn _can_receive(who: &T::AccountId, amount: T::Balance) -> bool {
+		amount >= <balances::Module<T>>::existential_deposit()
+			|| !<balances::Module<T>>::free_balance(who).is_zero()
+	}
+
+	/// `value * bps / 10_000`, rounded down, without overflowing on large values.
+	fn _bps(value: u128, bps: u32) -> u128 {
+		bonded_curve::bps(value, bps)
+	}
+
+	/// Whether trading `tokens` on `side` keeps the resulting spot price inside the
+	/// configured floor and ceiling.
+	fn _price_allowed(side: TradeSide, tokens: u128) -> bool {
+		let supply = Self::total_supply();
+		match side {
+			TradeSide::Buy => match (Self::max_price(), supply.checked_add(tokens)) {
+				(Some(max), Some(new_supply)) => Self::price_at(new_supply) <= max,
+				(Some(_), None) => false,
+				(None, _) => true,
+			},
+			TradeSide::Sell => match (Self::min_price(), supply.checked_sub(tokens)) {
+				(Some(min), Some(new_supply)) => Self::price_at(new_supply) >= min,
+				(Some(_), None) => false,
+				(None, _) => true,
+			},
+		}
+	}
+
+	/// The parameters of the configured curve.
+	pub fn curve_params() -> CurveParams {
+		CurveParams {
+			shape: Self::shape(),
+			exponent: Self::exponent(),
+			slope: Self::effective_slope(),
+			steepness: Self::steepness(),
+			base_price: Self::base_price(),
+		}
+	}
+
+	/// The configured slope, or during a launch phase the launch slope decayed
+	/// linearly towards it.
+	pub fn effective_slope() -> u128 {
+		let target = Self::slope();
+		let end = Self::launch_end();
+		let now = <system::Module<T>>::block_number();
+		if now >= end {
+			return target;
+		}
+
+		let remaining: u64 = (end - now).as_();
+		let duration: u64 = Self::launch_duration().as_();
+		let (remaining, duration) = (remaining as u128, duration as u128);
+		let excess = Self::launch_slope().saturating_sub(target);
+		target + excess / duration * remaining + excess % duration * remaining / duration
+	}
+
+	/// Marginal price of the configured curve at an arbitrary `supply`.
+	pub fn price_at(supply: u128) -> u128 {
+		Self::curve_params().spot_price(supply)
+	}
+
+	/// Applies the next scheduled curve step once it is due, moving the base price so
+	/// what sellers of the backed supply are owed on the new curve matches the reserve.
+	/// A step that even a zero base price leaves uncovered is held back and retried
+	/// every block until it can apply.
+	fn _apply_curve_schedule(now: T::BlockNumber) {
+		let mut schedule = Self::curve_schedule();
+		let (slope, exponent) = match schedule.first() {
+			Some((at, slope, exponent)) if *at <= now => (*slope, *exponent),
+			_ => return,
+		};
+
+		let params = match Self::_rebased(CurveParams { slope, exponent, ..Self::curve_params() }) {
+			Some(params) => params,
+			None => return,
+		};
+
+		schedule.remove(0);
+		<CurveSchedule<T>>::put(schedule);
+		<Slope<T>>::put(slope);
+		<Exponent<T>>::put(exponent);
+		<BasePrice<T>>::put(params.base_price);
+		<SpotPrice<T>>::put(params.spot_price(Self::total_supply()));
+
+		Self::deposit_event(RawEvent::CurveStepped(slope, exponent));
+	}
+
+	/// Moves the slope by the controller's output for this era, bounded by its maximum
+	/// step, rebasing the curve onto the reserve like a scheduled step. A change no
+	/// base price can cover is held back, and eras without an oracle price are skipped.
+	fn _tune_slope() {
+		let controller = match Self::slope_controller() {
+			Some(controller) => controller,
+			None => return,
+		};
+		let mut state = Self::slope_controller_state();
+		let volume = Self::cumulative_buy_volume().saturating_add(Self::cumulative_sell_volume());
+		let error = match controller.target {
+			ControlTarget::OraclePrice => match Self::oracle_price() {
+				Some(target) => Self::_deviation(target, Self::spot_price()),
+				None => return,
+			},
+			ControlTarget::Volume(target) => Self::_deviation(volume.saturating_sub(state.last_volume), target),
+		};
+
+		state.integral = cmp::max(cmp::min(state.integral + error, MAX_CONTROL_INTEGRAL), -MAX_CONTROL_INTEGRAL);
+		let output = (controller.kp as i64 * error
+			+ controller.ki as i64 * state.integral
+			+ controller.kd as i64 * (error - state.last_error)) / BPS as i64;
+		let step = cmp::max(cmp::min(output, controller.max_step as i64), -(controller.max_step as i64));
+		state.last_error = error;
+		state.last_volume = volume;
+		<SlopeControlState<T>>::put(state);
+
+		let old = Self::slope();
+		let change = Self::_bps(old, step.abs() as u32);
+		let slope = if step < 0 { old - change } else { old.saturating_add(change) };
+		if slope == old {
+			return;
+		}
+		let params = match Self::_rebased(CurveParams { slope, ..Self::curve_params() }) {
+			Some(params) => params,
+			None => return,
+		};
+
+		<Slope<T>>::put(slope);
+		<BasePrice<T>>::put(params.base_price);
+		<SpotPrice<T>>::put(params.spot_price(Self::total_supply()));
+		Self::deposit_event(RawEvent::SlopeTuned(old, slope));
+	}
+
+	/// `params` with the base price moved so what sellers of the backed supply are owed
+	/// matches the reserve, or `None` if no base price gets there or the result is not
+	/// a usable curve.
+	fn _rebased(params: CurveParams) -> Option<CurveParams> {
+		params.rebased(Self::_backed_supply(), Self::_total_reserve())
+			.filter(|params| params.validate(Self::max_supply()).is_ok())
 	}
-);
 
-/// All functions in the decl_module macro are part of the public interface of the module.
-impl<T: Trait> Module<T> {
-	/// Internal transfer function for ERC20 token.
-	fn _transfer(from: T::AccountId, to: T::AccountId, value: u128) -> Result {
-		ensure!(
-			<BalanceOf<T>>::exists(from.clone()),
-			"Account does not own any token."
-		);
+	/// How far `value` is above `reference`, in basis points of `reference` and capped
+	/// at 100% either way.
+	fn _deviation(value: u128, reference: u128) -> i64 {
+		if reference == 0 {
+			return if value > 0 { BPS as i64 } else { 0 };
+		}
+		if value >= reference {
+			cmp::min((value - reference).saturating_mul(BPS) / reference, BPS) as i64
+		} else {
+			-(cmp::min((reference - value).saturating_mul(BPS) / reference, BPS) as i64)
+		}
+	}
 
-		let sender_balance = Self::balance_of(from.clone());
+	/// Mints the era's inflation to the beneficiary, unless that would exceed the
+	/// maximum supply.
+	fn _mint_inflation() {
+		let beneficiary = match Self::inflation_beneficiary() {
+			Some(beneficiary) => beneficiary,
+			None => return,
+		};
+		let amount = Self::inflation_per_era();
+		if amount == 0 || Self::_mint(beneficiary.clone(), amount).is_err() {
+			return;
+		}
+
+		<InflationarySupply<T>>::mutate(|supply| *supply += amount);
+		Self::deposit_event(RawEvent::InflationMinted(beneficiary, amount));
+	}
+
+	/// Supply the reserve is expected to back: the total less inflationary tokens and
+	/// the unvested team allocation.
+	fn _backed_supply() -> u128 {
+		let now = <system::Module<T>>::block_number();
+		let unvested = Self::team_vesting().map_or(0, |schedule| schedule.locked_at(now));
+		Self::total_supply()
+			.saturating_sub(Self::inflationary_supply())
+			.saturating_sub(unvested)
+	}
+
+	/// Liquid and deployed reserve together.
+	fn _total_reserve() -> u128 {
+		Self::_from_balance(Self::reserve() + Self::deployed_reserve())
+	}
+
+	/// The oracle price, unless it is older than the configured maximum age. Features
+	/// comparing the curve to the market should read the feed through this.
+	pub fn oracle_price() -> Option<u128> {
+		let (price, reported) = T::PriceOracle::price()?;
+		let max_age = Self::oracle_max_age();
+		let now = <system::Module<T>>::block_number();
+		if !max_age.is_zero() && reported + max_age < now {
+			return None;
+		}
+		Some(price)
+	}
+
+	/// Largest supply at which the curve price does not exceed `price`, up to the
+	/// maximum supply.
+	fn _supply_for_price(price: u128) -> u128 {
+		let max = Self::max_supply();
+		let (mut lo, mut hi) = (0, if max == 0 { T::MAX_SUPPLY } else { max });
+		if Self::price_at(hi) <= price {
+			return hi;
+		}
+
+		while hi - lo > 1 {
+			let mid = lo + (hi - lo) / 2;
+			if Self::price_at(mid) <= price {
+				lo = mid;
+			} else {
+				hi = mid;
+			}
+		}
+		lo
+	}
+
+	/// Whether `supply` is within the maximum supply, if one is set.
+	fn _within_max_supply(supply: u128) -> bool {
+		let max = Self::max_supply();
+		max == 0 || supply <= max
+	}
+
+	/// Validates and writes the curve parameters, records `creator` as the royalty
+	/// beneficiary, mints any team allocation and marks the token as initialized.
+	fn _init_curve(
+		creator: T::AccountId,
+		params: CurveParams,
+		max_supply: u128,
+		team: Option<TeamAllocation<T::AccountId, T::BlockNumber>>
+	) -> Result {
 		ensure!(
-			sender_balance >= value,
-			"Not enough balance."
+			!Self::is_init(),
+			"Token is already initialized!"
 		);
+		params.validate(max_supply)?;
+		let team_amount = team.as_ref().map_or(0, |team| team.amount);
+		let supply = Self::total_supply().checked_add(team_amount).ok_or("Overflow while minting new tokens.")?;
+		ensure!(max_supply >= supply, "Maximum supply is below the current supply.");
+		ensure!(max_supply <= T::MAX_SUPPLY, "Maximum supply is above the runtime maximum.");
+		if let Some(team) = &team {
+			ensure!(!team.duration.is_zero(), "Team allocation must vest over at least one block.");
+			ensure!(team.cliff <= team.duration, "Team cliff is longer than the vesting period.");
+			ensure!(Self::vesting(&team.who).len() < MAX_VESTING_SCHEDULES, "Too many vesting schedules.");
+		}
 
-		let updated_from_balance = sender_balance.checked_sub(value).ok_or("Underflow in calculating balance.")?;
-		let receiver_balance = Self::balance_of(to.clone());
-		let updated_to_balance = receiver_balance.checked_add(value).ok_or("Overflow in calculating balance.")?;
+		<MaxSupply<T>>::put(max_supply);
+		<Exponent<T>>::put(params.exponent);
+		<Slope<T>>::put(params.slope);
+		<Shape<T>>::put(params.shape);
+		<Steepness<T>>::put(params.steepness);
+		<BasePrice<T>>::put(params.base_price);
+		<SpotPrice<T>>::put(params.spot_price(Self::total_supply()));
 
-		// Insert the updated balances into storage.
-		<BalanceOf<T>>::insert(from.clone(), updated_from_balance);
-		<BalanceOf<T>>::insert(to.clone(), updated_to_balance);
+		<Creator<T>>::put(creator);
+		<Init<T>>::put(true);
+
+		if let Some(team) = team {
+			if team.amount > 0 {
+				let now = <system::Module<T>>::block_number();
+				let duration: u64 = team.duration.as_();
+				let duration = duration as u128;
+				// Round up so the whole allocation has vested by the end of the duration.
+				let per_block = team.amount / duration + if team.amount % duration > 0 { 1 } else { 0 };
+				let schedule = VestingSchedule {
+					locked: team.amount,
+					per_block,
+					start: now,
+					cliff: now + team.cliff,
+				};
+				Self::_mint(team.who.clone(), team.amount)?;
+				Self::_mutate_account(&team.who, |account| account.vesting.push(schedule.clone()));
+				<TeamVesting<T>>::put(schedule);
+				Self::deposit_event(RawEvent::TeamAllocated(team.who, team.amount));
+			}
+		}
 
-		Self::deposit_event(RawEvent::Transfer(Some(from), Some(to), value));
 		Ok(())
 	}
 
-	/// Internal mint function for ERC20 token.
-	fn _mint(to: T::AccountId, amount: u128) -> Result {
-		let balance = Self::balance_of(&to);
+	/// Tokens of `who` that can be sold now: the balance less lots still locked after
+	/// purchase. Locked tokens can still be transferred.
+	pub fn sellable_balance(who: &T::AccountId) -> u128 {
+		let now = <system::Module<T>>::block_number();
+		let account = Self::account(who);
+		let balance = Self::balance_of(who);
+		let locked = account.buy_locks.iter()
+			.filter(|(unlock, _)| *unlock > now)
+			.fold(Self::_locked_in(&account, balance, now), |total, (_, tokens)| total.saturating_add(*tokens));
+		balance.saturating_sub(locked)
+	}
 
-		let new_balance = match balance.checked_add(amount) {
-			Some(x) => x,
-			None => return Err("Overflow while minting new tokens."),
-		};
+	/// Tokens of `who` that cannot move at all: those still vesting, locked behind
+	/// vetoes, delegated, staked or held as collateral, capped at the balance.
+	fn _locked(who: &T::AccountId) -> u128 {
+		Self::_locked_in(&Self::account(who), Self::balance_of(who), <system::Module<T>>::block_number())
+	}
+
+	/// Tokens that cannot move out of an account with the given state and balance.
+	fn _locked_in(account: &AccountData<T::AccountId, T::BlockNumber>, balance: u128, now: T::BlockNumber) -> u128 {
+		let delegated = account.delegation.as_ref().map_or(0, |(_, tokens)| *tokens);
+		let locked = account.vesting.iter()
+			.fold(0u128, |total, schedule| total.saturating_add(schedule.locked_at(now)))
+			.saturating_add(account.veto_locked)
+			.saturating_add(delegated)
+			.saturating_add(account.staked)
+			.saturating_add(account.collateral);
+		cmp::min(locked, balance)
+	}
+
+	/// Checks that `delegated` power can be taken back from `delegate` without
+	/// pulling it out from under its vetoes.
+	fn _ensure_undelegatable(delegate: &T::AccountId, delegated: u128) -> Result {
+		let unlocked = Self::delegated_power(delegate).saturating_sub(Self::delegated_power_locked(delegate));
+		ensure!(unlocked >= delegated, "Delegated power is locked behind the delegate's vetoes.");
+		Ok(())
+	}
+
+	/// Tokens of `who` still locked by vesting schedules, capped at the balance.
+	pub fn vesting_locked(who: &T::AccountId) -> u128 {
+		let now = <system::Module<T>>::block_number();
+		let locked = Self::vesting(who).iter()
+			.fold(0u128, |total, schedule| total.saturating_add(schedule.locked_at(now)));
+		cmp::min(locked, Self::balance_of(who))
+	}
+
+	/// Records a lot of bought tokens that unlocks after the lock period, dropping
+	/// lots that have already unlocked.
+	fn _lock_bought(who: &T::AccountId, tokens: u128) {
+		let period = Self::buy_lock_period();
+		if period.is_zero() {
+			return;
+		}
+
+		let now = <system::Module<T>>::block_number();
+		let unlock = now + period;
+		let mut lots: Vec<_> = Self::buy_locks(who).into_iter().filter(|(at, _)| *at > now).collect();
+		match lots.last_mut() {
+			Some((at, locked)) if *at == unlock => *locked = locked.saturating_add(tokens),
+			_ => lots.push((unlock, tokens)),
+		}
+		Self::_mutate_account(who, |account| account.buy_locks = lots);
+	}
+
+	/// Whether the sell cooldown since the latest buy of `who` has passed.
+	fn _cooled_down(who: &T::AccountId) -> bool {
+		match Self::last_buy(who) {
+			Some(bought) => <system::Module<T>>::block_number() >= bought + Self::sell_cooldown(),
+			None => true,
+		}
+	}
+
+	/// Whether `who` may buy `tokens` more without exceeding the purchase cap.
+	fn _within_purchase_cap(who: &T::AccountId, tokens: u128) -> bool {
+		match Self::purchase_cap() {
+			Some(cap) => Self::purchased(who).checked_add(tokens).map_or(false, |total| total <= cap),
+			None => true,
+		}
+	}
 
+	/// Cost of buying `tokens` from the curve at the current supply, excluding the
+	/// entry tribute. `None` if the purchase would overflow or exceed the maximum supply.
+	///
+	/// Rounded up, so the buyer always pays at least the exact area under the curve.
+	pub fn cost_to_buy(tokens: u128) -> Option<u128> {
 		let supply = Self::total_supply();
-		
-		let new_supply = match supply.checked_add(amount) {
-			Some(x) => x,
-			None => return Err("Overflow while minting new tokens."),
-		};
+		let new_supply = supply.checked_add(tokens)?;
+		if !Self::_within_max_supply(new_supply) {
+			return None;
+		}
+		Self::curve_params().area(supply, new_supply, true)
+	}
 
-		<TotalSupply<T>>::put(new_supply);
-		<BalanceOf<T>>::insert(to.clone(), new_balance);
+	/// Trie keys the balance of `who` may be stored under, so light clients can prove
+	/// it against a state root without knowing how keys are hashed.
+	pub fn balance_keys(who: &T::AccountId) -> StorageKeys {
+		StorageKeys {
+			current: runtime_io::twox_128(&<Balances<T>>::key_for(T::Hashing::hash_of(who))).to_vec(),
+			legacy: runtime_io::twox_128(&<BalanceOf<T>>::key_for(who)).to_vec(),
+		}
+	}
 
-		Self::deposit_event(RawEvent::Transfer(None, Some(to), amount));
-		Ok(())
+	/// Trie keys the allowance of `(owner, spender)` may be stored under.
+	pub fn allowance_keys(key: (T::AccountId, T::AccountId)) -> StorageKeys {
+		StorageKeys {
+			current: runtime_io::twox_128(&<Allowances<T>>::key_for(T::Hashing::hash_of(&key))).to_vec(),
+			legacy: runtime_io::twox_128(&<Allowance<T>>::key_for(key)).to_vec(),
+		}
 	}
 
-	/// Internal burn function for Erc20 token.
-	fn _burn(from: T::AccountId, amount: u128) -> Result {
-		let balance = Self::balance_of(&from);
+	/// Summary of the token's state for wallets.
+	pub fn token_state() -> TokenState {
+		TokenState {
+			supply: Self::total_supply(),
+			reserve: Self::_total_reserve(),
+			spot_price: Self::spot_price(),
+			curve: Self::curve_params(),
+			entry_fee: Self::entry_fee(),
+			exit_fee: Self::exit_fee(),
+			fees_enabled: Self::fees_enabled(),
+			paused: !Self::is_init() || Self::auction_open() || Self::paused(),
+		}
+	}
 
-		let new_balance = match balance.checked_sub(amount) {
-			Some(x) => x,
-			None => return Err("Underflow while burning tokens."),
-		};
+	/// Full cost breakdown of buying `tokens` now, or `None` if the buy is impossible.
+	pub fn estimate_buy(tokens: u128) -> Option<TradeQuote> {
+		let cost = Self::cost_to_buy(tokens)?;
+		let tribute = Self::_bps(cost, Self::_entry_rate(tokens));
+		Some(TradeQuote {
+			gross: cost.checked_add(tribute)?,
+			tribute,
+			net: cost,
+			spot_price: Self::price_at(Self::total_supply() + tokens),
+		})
+	}
+
+	/// Full payout breakdown of selling `tokens` now, or `None` if the sell is impossible.
+	pub fn estimate_sell(tokens: u128) -> Option<TradeQuote> {
+		let ret_amount = Self::return_on_sell(tokens)?;
+		let tribute = Self::_bps(ret_amount, Self::_exit_rate(None, tokens));
+		Some(TradeQuote {
+			gross: ret_amount,
+			tribute,
+			net: ret_amount - tribute,
+			spot_price: Self::price_at(Self::total_supply() - tokens),
+		})
+	}
 
+	/// Amount returned for selling `tokens` to the curve at the current supply.
+	/// `None` if more tokens are sold than exist.
+	///
+	/// Rounded down, so the seller never receives more than the exact area under the
+	/// curve. Together with `cost_to_buy` rounding up, buying and then selling the same
+	/// tokens can never return more than was paid.
+	pub fn return_on_sell(tokens: u128) -> Option<u128> {
 		let supply = Self::total_supply();
+		let new_supply = supply.checked_sub(tokens)?;
+		Self::curve_params().area(new_supply, supply, false)
+	}
+}
 
-		let new_supply = match supply.checked_sub(amount) {
-			Some(x) => x,
-			None => return Err("Underflow while burning tokens."),
+/// Lets another bonded token module use this one as its conversion counterpart.
+impl<T: Trait> BondedCurve<T::AccountId> for Module<T> {
+	fn quote_buy(value: u128) -> u128 {
+		Self::_tokens_for_spend(value)
+	}
+
+	fn buy_with(who: &T::AccountId, value: u128, min_tokens: u128) -> Result {
+		let tokens = Self::_tokens_for_spend(value);
+		ensure!(tokens >= min_tokens, "Conversion returns less than the minimum.");
+		let result = Self::_buy(who.clone(), who.clone(), tokens, Some(value), None);
+		Self::_check_invariants(&[]);
+		result
+	}
+
+	fn ensure_can_buy(who: &T::AccountId, value: u128, min_tokens: u128) -> Result {
+		let tokens = Self::_tokens_for_spend(value);
+		ensure!(tokens >= min_tokens, "Conversion returns less than the minimum.");
+		Self::_check_buy(who, tokens, Some(value)).map(|_| ())
+	}
+}
+
+/// Lets authorized modules penalize holders of this token.
+impl<T: Trait> SlashTokens<T::AccountId> for Module<T> {
+	fn slash(who: &T::AccountId, amount: u128, insure: bool) -> u128 {
+		let slashed = cmp::min(amount, Self::balance_of(who));
+		if slashed == 0 {
+			return 0;
+		}
+		// What the curve would have paid for the tokens, capped at what is liquid.
+		let backing = if insure {
+			let value = Self::return_on_sell(slashed).unwrap_or(0);
+			cmp::min(Self::_saturating_balance(value), Self::reserve())
+		} else {
+			Zero::zero()
 		};
+		if Self::_burn(who.clone(), slashed).is_err() {
+			return 0;
+		}
+		<Reserve<T>>::mutate(|reserve| *reserve -= backing);
+		<InsuranceFund<T>>::mutate(|fund| *fund += backing);
+
+		Self::deposit_event(RawEvent::Slashed(who.clone(), slashed, backing));
+		Self::_check_invariants(&[]);
+		slashed
+	}
+}
 
-		<TotalSupply<T>>::put(new_supply);
-		<BalanceOf<T>>::insert(from.clone(), new_balance);
+/// Lets a staking-style module hold stake in this token.
+impl<T: Trait> StakeTokens<T::AccountId> for Module<T> {
+	fn staked(who: &T::AccountId) -> u128 {
+		Self::staked(who)
+	}
 
-		Self::deposit_event(RawEvent::Transfer(Some(from), None, amount));
+	fn stake(who: &T::AccountId, amount: u128) -> Result {
+		let free = Self::balance_of(who).saturating_sub(Self::_locked(who));
+		ensure!(free >= amount, "Not enough unlocked balance.");
+		Self::_mutate_account(who, |account| account.staked += amount);
+		Self::deposit_event(RawEvent::Staked(who.clone(), amount));
 		Ok(())
 	}
 
-	fn _integral(to_x: u128) -> u128 {
-		let nexp = match Self::exponent().checked_add(1) {
-			Some(x) => x,
-			None => return 0,
-		};
+	fn unstake(who: &T::AccountId, amount: u128) -> u128 {
+		let unstaked = cmp::min(amount, Self::staked(who));
+		Self::_mutate_account(who, |account| account.staked -= unstaked);
+		Self::deposit_event(RawEvent::Unstaked(who.clone(), unstaked));
+		unstaked
+	}
+
+	fn slash_stake(who: &T::AccountId, amount: u128) -> u128 {
+		let slashed = <Self as SlashTokens<T::AccountId>>::slash(who, cmp::min(amount, Self::staked(who)), true);
+		Self::_mutate_account(who, |account| account.staked -= slashed);
+		slashed
+	}
+}
+
+/// Lets a lending module take this token as collateral, liquidated on the curve.
+impl<T: Trait> CollateralAsset<T::AccountId> for Module<T> {
+	fn collateral(who: &T::AccountId) -> u128 {
+		Self::collateral(who)
+	}
+
+	fn lock_collateral(who: &T::AccountId, amount: u128) -> Result {
+		let free = Self::balance_of(who).saturating_sub(Self::_locked(who));
+		ensure!(free >= amount, "Not enough unlocked balance.");
+		Self::_mutate_account(who, |account| account.collateral += amount);
+		Self::deposit_event(RawEvent::CollateralLocked(who.clone(), amount));
+		Ok(())
+	}
+
+	fn unlock_collateral(who: &T::AccountId, amount: u128) -> u128 {
+		let unlocked = cmp::min(amount, Self::collateral(who));
+		Self::_mutate_account(who, |account| account.collateral -= unlocked);
+		Self::deposit_event(RawEvent::CollateralUnlocked(who.clone(), unlocked));
+		unlocked
+	}
 
-		let slope = Self::slope();
+	fn collateral_value(amount: u128) -> u128 {
+		Self::_sell_payout(None, amount).unwrap_or(0)
+	}
+
+	fn liquidate(who: &T::AccountId, amount: u128, to: &T::AccountId) -> rstd::result::Result<u128, &'static str> {
+		ensure!(Self::collateral(who) >= amount, "Not enough collateral.");
+		let proceeds = Self::_sell_payout(Some(who), amount).ok_or("Underflow while selling tokens.")?;
 
-		match (to_x ** &nexp).checked_mul(slope).unwrap().checked_div(nexp) {
-			Some(x) => return x,
-			None => return 0,
+		// The sell only sees unlocked tokens, and writes nothing if it fails.
+		Self::_mutate_account(who, |account| account.collateral -= amount);
+		if let Err(e) = Self::_sell(who.clone(), to.clone(), amount) {
+			Self::_mutate_account(who, |account| account.collateral += amount);
+			return Err(e);
 		}
+
+		Self::deposit_event(RawEvent::CollateralLiquidated(who.clone(), amount, proceeds));
+		Self::_check_invariants(&[]);
+		Ok(proceeds)
+	}
+}
+
+impl<T: Trait> OnUnbalanced<T::Balance> for Module<T> {
+	fn on_unbalanced(amount: T::Balance) {
+		<FundingPool<T>>::mutate(|pool| *pool += amount);
 	}
 }
 
 // tests for this module
-// #[cfg(test)]
-// mod tests {
-// 	use super::*;
-
-// 	use runtime_io::with_externalities;
-// 	use primitives::{H256, Blake2Hasher};
-// 	use support::{impl_outer_origin, assert_ok};
-// 	use runtime_primitives::{
-// 		BuildStorage,
-// 		traits::{BlakeTwo256, IdentityLookup},
-// 		testing::{Digest, DigestItem, Header}
-// 	};
-
-// 	impl_outer_origin! {
-// 		pub enum Origin for Test {}
-// 	}
-
-// 	// For testing the module, we construct most of a mock runtime. This means
-// 	// first constructing a configuration type (`Test`) which `impl`s each of the
-// 	// configuration traits of modules we want to use.
-// 	#[derive(Clone, Eq, PartialEq)]
-// 	pub struct Test;
-// 	impl system::Trait for Test {
-// 		type Origin = Origin;
-// 		type Index = u64;
-// 		type BlockNumber = u64;
-// 		type Hash = H256;
-// 		type Hashing = BlakeTwo256;
-// 		type Digest = Digest;
-// 		type AccountId = u64;
-// 		type Lookup = IdentityLookup<u64>;
-// 		type Header = Header;
-// 		type Event = ();
-// 		type Log = DigestItem;
-// 	}
-// 	impl Trait for Test {
-// 		type Event = ();
-// 	}
-// 	type BondedFungibleToken = Module<Test>;
-
-// 	// This function basically just builds a genesis storage key/value store according to
-// 	// our desired mockup.
-// 	fn new_test_ext() -> runtime_io::TestExternalities<Blake2Hasher> {
-// 		system::GenesisConfig::<Test>::default().build_storage().unwrap().0.into()
-// 	}
-
-// 	#[test]
-// 	fn it_works_for_default_value() {
-// 		with_externalities(&mut new_test_ext(), || {
-// 			// Just a dummy test for the dummy funtion `do_something`
-// 			// calling the `do_something` function with a value 42
-// 			assert_ok!(BondedFungibleToken::do_something(Origin::signed(1), 42));
-// 			// asserting that the stored value is equal to what we stored
-// 			assert_eq!(BondedFungibleToken::something(), Some(42));
-// 		});
-// 	}
-// }
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use runtime_io::with_externalities;
+	use primitives::{H256, Blake2Hasher};
+	use support::{impl_outer_origin, assert_ok, assert_noop};
+	use runtime_primitives::{
+		BuildStorage,
+		traits::{BlakeTwo256, IdentityLookup, OnInitialise, OnFinalise},
+		testing::{Digest, DigestItem, Header}
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for Test {}
+	}
+
+	// For testing the module, we construct most of a mock runtime. This means
+	// first constructing a configuration type (`Test`) which `impl`s each of the
+	// configuration traits of modules we want to use.
+	#[derive(Clone, Eq, PartialEq)]
+	pub struct Test;
+	impl system::Trait for Test {
+		type Origin = Origin;
+		type Index = u64;
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type Digest = Digest;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<u64>;
+		type Header = Header;
+		type Event = ();
+		type Log = DigestItem;
+	}
+	impl consensus::Trait for Test {
+		type Log = DigestItem;
+		type SessionKey = u64;
+		type InherentOfflineReport = ();
+	}
+	impl timestamp::Trait for Test {
+		type Moment = u64;
+		type OnTimestampSet = ();
+	}
+	impl balances::Trait for Test {
+		type Balance = u128;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type EnsureAccountLiquid = ();
+		type Event = ();
+	}
+	impl Trait for Test {
+		type TokenBalance = u128;
+		type Event = ();
+		type OnTokenReceived = ();
+		type OnBuy = ();
+		type OnSell = ();
+		type ReserveManager = ();
+		type Counterpart = ();
+		type PriceOracle = ();
+		type PriceFeed = ();
+		type FlashBorrower = Borrower;
+		type FeeHandler = ();
+		type BalanceConversion = IdentityConversion;
+
+		const MIN_TRADE: u128 = 1;
+		const MAX_ENTRY_FEE: u32 = 1_000;
+		const MAX_EXIT_FEE: u32 = 1_000;
+		const MAX_SUPPLY: u128 = 1_000_000_000;
+		const MAX_ALLOWANCES: u32 = 32;
+		const TRACK_COST_BASIS: bool = true;
+	}
+	type System = system::Module<Test>;
+	type Balances = balances::Module<Test>;
+	type BondedToken = Module<Test>;
+
+	// Repays a flash mint when asked to and otherwise sends the tokens to account 9.
+	pub struct Borrower;
+	impl FlashBorrower<u64> for Borrower {
+		fn on_flash_mint(who: &u64, amount: u128, _fee: u128, data: &[u8]) -> Result {
+			match data {
+				b"repay" => Ok(()),
+				b"default" => BondedToken::transfer(Origin::signed(*who), 9, amount),
+				_ => Err("Unknown strategy."),
+			}
+		}
+	}
+
+	// This function basically just builds a genesis storage key/value store according to
+	// our desired mockup.
+	fn new_test_ext() -> runtime_io::TestExternalities<Blake2Hasher> {
+		test_ext_with_deposit(0)
+	}
+
+	fn test_ext_with_deposit(existential_deposit: u128) -> runtime_io::TestExternalities<Blake2Hasher> {
+		let mut t = system::GenesisConfig::<Test>::default().build_storage().unwrap().0;
+		t.extend(balances::GenesisConfig::<Test> {
+			balances: vec![(1, 1_000_000_000), (2, 1_000_000_000), (3, 1_000_000_000), (4, 1_000_000_000)],
+			existential_deposit,
+			..Default::default()
+		}.build_storage().unwrap().0);
+		t.extend(GenesisConfig::<Test>::default().build_storage().unwrap().0);
+		t.into()
+	}
+
+	// A linear curve, `price = 3 * supply + 1`, created by account 1.
+	fn init_curve() {
+		assert_ok!(BondedToken::init(Origin::signed(1), 1, 3, 1, 1_000_000, None));
+	}
+
+	#[test]
+	fn buying_then_selling_never_pays_out_more_than_it_cost() {
+		with_externalities(&mut new_test_ext(), || {
+			init_curve();
+			// Account 1 holds some supply so the round trips start part way up the curve.
+			assert_ok!(BondedToken::buy(Origin::signed(1), 37, None));
+
+			for &tokens in &[1, 2, 3, 10, 999] {
+				let before = Balances::free_balance(&2);
+				assert_ok!(BondedToken::buy(Origin::signed(2), tokens, None));
+				assert_eq!(BondedToken::balance_of(2), tokens);
+				assert_ok!(BondedToken::sell(Origin::signed(2), tokens, None));
+				assert_eq!(BondedToken::balance_of(2), 0);
+				assert!(Balances::free_balance(&2) <= before);
+			}
+
+			// Selling the supply back one token at a time cannot drain the reserve either.
+			for _ in 0..37 {
+				assert_ok!(BondedToken::sell(Origin::signed(1), 1, None));
+			}
+			assert_eq!(BondedToken::total_supply(), 0);
+		});
+	}
+	#[test]
+	fn holders_are_counted_once_under_the_hashed_key() {
+		with_externalities(&mut new_test_ext(), || {
+			init_curve();
+			assert_ok!(BondedToken::buy(Origin::signed(1), 10, None));
+			assert_eq!(BondedToken::holder_count(), 1);
+
+			// A balance stored before the upgrade was never counted, so emptying it must
+			// not take the count below the holders that were.
+			<BalanceOf<Test>>::insert(3, 5);
+			assert_ok!(BondedToken::transfer(Origin::signed(3), 4, 5));
+			assert_eq!(BondedToken::holder_count(), 2);
+			assert_ok!(BondedToken::transfer(Origin::signed(4), 1, 5));
+			assert_eq!(BondedToken::holder_count(), 1);
+
+			// Migrating a legacy balance counts its holder without changing it.
+			<BalanceOf<Test>>::insert(5, 7);
+			assert_ok!(BondedToken::migrate_balances(Origin::signed(2), vec![5, 6]));
+			assert_eq!(BondedToken::holder_count(), 2);
+			assert_eq!(BondedToken::balance_of(5), 7);
+			assert_eq!(BondedToken::legacy_balance_of(5), 0);
+		});
+	}
+
+	#[test]
+	fn reaping_needs_a_signed_origin_and_an_empty_entry() {
+		with_externalities(&mut new_test_ext(), || {
+			<BalanceOf<Test>>::insert(3, 0);
+			<BalanceOf<Test>>::insert(4, 2);
+			assert!(BondedToken::reap(system::RawOrigin::Root.into(), 3).is_err());
+			assert_noop!(BondedToken::reap(Origin::signed(1), 4), "Account still holds tokens.");
+			assert_noop!(BondedToken::reap(Origin::signed(1), 5), "Account has no balance entry.");
+			assert_ok!(BondedToken::reap(Origin::signed(1), 3));
+			assert!(!BondedToken::_has_balance_entry(&3));
+		});
+	}
+	#[test]
+	fn legacy_balances_are_audited_after_an_upgrade() {
+		with_externalities(&mut new_test_ext(), || {
+			init_curve();
+			assert_ok!(BondedToken::buy(Origin::signed(1), 10, None));
+
+			// A chain upgraded with a holder whose balance is still under the legacy key.
+			<BalanceOf<Test>>::insert(3, 5);
+			<TotalSupply<Test>>::put(15);
+			<LegacyTotalSeeded<Test>>::put(false);
+			assert!(BondedToken::audit_supply().is_err());
+
+			BondedToken::on_initialise(1);
+			assert_eq!(BondedToken::legacy_balance_total(), 5);
+			assert_ok!(BondedToken::audit_supply());
+
+			// Moving the legacy balance keeps the totals in step.
+			assert_ok!(BondedToken::transfer(Origin::signed(3), 4, 2));
+			assert_eq!(BondedToken::legacy_balance_total(), 0);
+			assert_eq!(BondedToken::balance_total(), 15);
+			assert_ok!(BondedToken::audit_supply());
+		});
+	}
+	#[test]
+	fn a_matched_fill_is_charted_once() {
+		with_externalities(&mut new_test_ext(), || {
+			init_curve();
+			<CandleLength<Test>>::put(10);
+			<CandleRetention<Test>>::put(4);
+			<TradeHistorySize<Test>>::put(8);
+			assert_ok!(BondedToken::buy(Origin::signed(1), 10, None));
+			let volume = BondedToken::candle(0).unwrap().volume;
+			let cursor = BondedToken::trade_history_cursor();
+
+			// Both orders cross at the spot price of 31.
+			assert_ok!(BondedToken::queue_sell(Origin::signed(1), 5, 0));
+			assert_ok!(BondedToken::queue_buy(Origin::signed(2), 5, 100));
+			assert_ok!(BondedToken::execute_orders(Origin::signed(3)));
+
+			assert_eq!(BondedToken::balance_of(2), 5);
+			assert_eq!(BondedToken::candle(0).unwrap().volume, volume + 5 * 31);
+			assert_eq!(BondedToken::trade_history_cursor(), cursor + 1);
+			let record = BondedToken::trade_history(cursor).unwrap();
+			assert_eq!(record.account, <Test as system::Trait>::Hashing::hash_of(&2));
+			assert_eq!(record.counterparty, Some(<Test as system::Trait>::Hashing::hash_of(&1)));
+		});
+	}
+	#[test]
+	fn a_recurring_buy_pays_the_same_tribute_as_a_buy() {
+		with_externalities(&mut new_test_ext(), || {
+			init_curve();
+			<FeesEnabled<Test>>::put(true);
+			<EntryFee<Test>>::put(100);
+			// Buying the whole supply carries a surcharge that counting the purchase
+			// twice would miss.
+			<FeeTiers<Test>>::put(vec![(6_000, 500)]);
+
+			let tokens = BondedToken::_tokens_for_spend(10_000);
+			let cost = BondedToken::cost_to_buy(tokens).unwrap();
+			let fee = cost * 600 / 10_000;
+			assert_ok!(BondedToken::schedule_recurring_buy(Origin::signed(2), 10_000, 1, 1));
+			BondedToken::on_initialise(1);
+
+			assert_eq!(BondedToken::balance_of(2), tokens);
+			assert_eq!(Balances::free_balance(&2), 1_000_000_000 - cost - fee);
+			assert_eq!(BondedToken::recurring_buy(0), None);
+		});
+	}
+
+	#[test]
+	fn a_recurring_buy_skips_a_paused_curve() {
+		with_externalities(&mut new_test_ext(), || {
+			init_curve();
+			assert_ok!(BondedToken::schedule_recurring_buy(Origin::signed(2), 10_000, 1, 2));
+			<Paused<Test>>::put(0);
+			BondedToken::on_initialise(1);
+			assert_eq!(BondedToken::balance_of(2), 0);
+			assert_eq!(BondedToken::recurring_buy(0).unwrap().remaining, 1);
+
+			<Paused<Test>>::kill();
+			BondedToken::on_initialise(2);
+			assert!(BondedToken::balance_of(2) > 0);
+		});
+	}
+
+	#[test]
+	fn recurring_buys_due_in_one_block_are_capped() {
+		with_externalities(&mut new_test_ext(), || {
+			init_curve();
+			for _ in 0..MAX_RECURRING_DUE {
+				assert_ok!(BondedToken::schedule_recurring_buy(Origin::signed(2), 100, 1, 1));
+			}
+			// The next plan falls due in the following block instead.
+			assert_ok!(BondedToken::schedule_recurring_buy(Origin::signed(3), 100, 1, 1));
+			assert_eq!(BondedToken::recurring_buys_due(1).len(), MAX_RECURRING_DUE);
+			assert_eq!(BondedToken::recurring_buys_due(2), vec![MAX_RECURRING_DUE as u64]);
+		});
+	}
+	#[test]
+	fn topping_up_resets_the_holding_age_by_weight() {
+		with_externalities(&mut new_test_ext(), || {
+			init_curve();
+			<LoyaltyTiers<Test>>::put(vec![(100, 100)]);
+			assert_ok!(BondedToken::buy(Origin::signed(2), 1, None));
+			assert_eq!(BondedToken::account(2).held_since, Some(0));
+
+			// A holder of one token for 200 blocks who buys 99 more holds on average
+			// from block 198 and does not qualify.
+			System::set_block_number(200);
+			assert_eq!(BondedToken::_loyalty_rate(&2), 100);
+			assert_ok!(BondedToken::buy(Origin::signed(2), 99, None));
+			assert_eq!(BondedToken::account(2).held_since, Some(198));
+			assert_eq!(BondedToken::_loyalty_rate(&2), u32::max_value());
+
+			// Selling keeps the holding age; a holder from before tracking never qualifies.
+			assert_ok!(BondedToken::sell(Origin::signed(2), 50, None));
+			assert_eq!(BondedToken::account(2).held_since, Some(198));
+			<BalanceOf<Test>>::insert(3, 10);
+			assert_eq!(BondedToken::_loyalty_rate(&3), u32::max_value());
+		});
+	}
+	#[test]
+	fn referral_rewards_are_claimed_above_the_existential_deposit() {
+		with_externalities(&mut test_ext_with_deposit(1_000), || {
+			init_curve();
+			<FeesEnabled<Test>>::put(true);
+			<EntryFee<Test>>::put(1_000);
+			<ReferralReward<Test>>::put(5_000);
+
+			// Account 9 holds no currency, so a reward below the deposit stays owed.
+			assert_ok!(BondedToken::buy(Origin::signed(2), 10, Some(9)));
+			let owed = BondedToken::referral_rewards(9);
+			assert!(owed > 0 && owed < 1_000);
+			assert_eq!(Balances::free_balance(&9), 0);
+			assert_noop!(BondedToken::claim_referral_rewards(Origin::signed(9)), "Reward is below the existential deposit.");
+
+			assert_ok!(BondedToken::buy(Origin::signed(2), 1_000, Some(9)));
+			let owed = BondedToken::referral_rewards(9);
+			assert!(owed >= 1_000);
+			assert_ok!(BondedToken::claim_referral_rewards(Origin::signed(9)));
+			assert_eq!(Balances::free_balance(&9), owed);
+			assert_eq!(BondedToken::referral_rewards(9), 0);
+		});
+	}
+	#[test]
+	fn beneficiary_shares_are_split_by_weight_and_claimed() {
+		with_externalities(&mut test_ext_with_deposit(1_000), || {
+			init_curve();
+			<FeesEnabled<Test>>::put(true);
+			<EntryFee<Test>>::put(1_000);
+			<Beneficiaries<Test>>::put(vec![(8, 1), (9, 3)]);
+
+			assert_ok!(BondedToken::buy(Origin::signed(2), 1_000, None));
+			let (eight, nine) = (BondedToken::beneficiary_shares(8), BondedToken::beneficiary_shares(9));
+			assert!(eight >= 1_000);
+			assert!(nine / 3 >= eight - 1 && nine / 3 <= eight + 1);
+			assert_eq!(Balances::free_balance(&8), 0);
+
+			assert_ok!(BondedToken::claim_beneficiary_share(Origin::signed(8)));
+			assert_eq!(Balances::free_balance(&8), eight);
+			assert_noop!(BondedToken::claim_beneficiary_share(Origin::signed(8)), "No beneficiary share to claim.");
+		});
+	}
+	#[test]
+	fn creator_fees_wait_until_they_reach_the_existential_deposit() {
+		with_externalities(&mut test_ext_with_deposit(1_000), || {
+			assert_ok!(BondedToken::init(Origin::signed(9), 1, 3, 1, 1_000_000, None));
+			<FeesEnabled<Test>>::put(true);
+			<EntryFee<Test>>::put(1_000);
+			<CreatorShare<Test>>::put(1_000);
+
+			assert_noop!(BondedToken::claim_creator_fees(Origin::signed(9)), "No creator fees to claim.");
+			assert_ok!(BondedToken::buy(Origin::signed(2), 10, None));
+			assert_noop!(
+				BondedToken::claim_creator_fees(Origin::signed(9)),
+				"Creator fees are below the existential deposit."
+			);
+
+			assert_ok!(BondedToken::buy(Origin::signed(2), 1_000, None));
+			let owed = BondedToken::creator_fees();
+			assert_ok!(BondedToken::claim_creator_fees(Origin::signed(9)));
+			assert_eq!(Balances::free_balance(&9), owed);
+			assert_eq!(BondedToken::creator_fees(), 0);
+		});
+	}
+	#[test]
+	fn only_the_spender_can_use_its_allowance() {
+		with_externalities(&mut new_test_ext(), || {
+			init_curve();
+			assert_ok!(BondedToken::buy(Origin::signed(1), 10, None));
+			assert_ok!(BondedToken::approve(Origin::signed(1), 2, 6));
+
+			assert_noop!(
+				BondedToken::transfer_from(Origin::signed(3), 1, 2, 4),
+				"Only the spender can use its allowance."
+			);
+			assert!(BondedToken::transfer_from(system::RawOrigin::Root.into(), 1, 2, 4).is_err());
+			assert_ok!(BondedToken::transfer_from(Origin::signed(2), 1, 2, 4));
+			assert_eq!(BondedToken::balance_of(2), 4);
+			assert_eq!(BondedToken::allowance((1, 2)), 2);
+			assert_noop!(BondedToken::transfer_from(Origin::signed(2), 1, 2, 4), "Not enough allowance.");
+		});
+	}
+	#[test]
+	fn buyback_buys_on_the_curve_and_burns() {
+		with_externalities(&mut new_test_ext(), || {
+			init_curve();
+			<CandleLength<Test>>::put(10);
+			assert_ok!(BondedToken::buy(Origin::signed(2), 100, None));
+			<FundingPool<Test>>::put(1_000_000);
+			let (supply, reserve) = (BondedToken::total_supply(), BondedToken::reserve());
+			let volume = BondedToken::candle(0).unwrap().volume;
+
+			let tokens = BondedToken::_tokens_for_spend(100_000);
+			let cost = BondedToken::cost_to_buy(tokens).unwrap();
+			assert_noop!(BondedToken::buyback(Origin::signed(2), 100_000), "Origin must be root or the admin.");
+			assert_ok!(BondedToken::buyback(system::RawOrigin::Root.into(), 100_000));
+
+			assert_eq!(BondedToken::total_supply(), supply);
+			assert_eq!(BondedToken::balance_of(0), 0);
+			assert_eq!(BondedToken::reserve(), reserve + cost);
+			assert_eq!(BondedToken::funding_pool(), 1_000_000 - cost);
+			assert_eq!(BondedToken::candle(0).unwrap().volume, volume + cost);
+			assert_eq!(BondedToken::account(0), AccountData::default());
+			assert_ok!(BondedToken::audit_solvency());
+		});
+	}
+	#[test]
+	fn queued_orders_net_before_the_curve() {
+		with_externalities(&mut new_test_ext(), || {
+			init_curve();
+			assert_ok!(BondedToken::buy(Origin::signed(1), 10, None));
+
+			// Four tokens cross at the spot price of 31, and the curve sells the other two
+			// for 3 * (12^2 - 10^2) / 2 + 2.
+			assert_ok!(BondedToken::queue_sell(Origin::signed(1), 4, 0));
+			assert_ok!(BondedToken::queue_buy(Origin::signed(2), 6, 40));
+			assert_ok!(BondedToken::execute_orders(Origin::signed(3)));
+
+			assert_eq!(BondedToken::balance_of(1), 6);
+			assert_eq!(BondedToken::balance_of(2), 6);
+			assert_eq!(BondedToken::total_supply(), 12);
+			assert_eq!(Balances::free_balance(&2), 1_000_000_000 - 4 * 31 - 68);
+			assert!(BondedToken::buy_queue().is_empty());
+			assert!(BondedToken::sell_queue().is_empty());
+		});
+	}
+
+	#[test]
+	fn an_order_past_its_limit_rests() {
+		with_externalities(&mut new_test_ext(), || {
+			init_curve();
+			assert_ok!(BondedToken::buy(Origin::signed(1), 10, None));
+
+			assert_ok!(BondedToken::queue_sell(Origin::signed(1), 2, 1_000));
+			assert!(BondedToken::execute_orders(Origin::signed(3)).is_err());
+			let queue = BondedToken::sell_queue();
+			assert_eq!(queue.len(), 1);
+			assert!(queue[0].resting);
+			assert_eq!(BondedToken::balance_of(1), 8);
+
+			assert_ok!(BondedToken::cancel_orders(Origin::signed(1)));
+			assert!(BondedToken::sell_queue().is_empty());
+			assert_eq!(BondedToken::balance_of(1), 10);
+		});
+	}
+
+	#[test]
+	fn the_order_queues_are_bounded() {
+		with_externalities(&mut new_test_ext(), || {
+			init_curve();
+			for _ in 0..MAX_ORDERS {
+				assert_ok!(BondedToken::queue_buy(Origin::signed(2), 1, 1));
+			}
+			assert_noop!(BondedToken::queue_buy(Origin::signed(3), 1, 1), "Buy queue is full.");
+		});
+	}
+	#[test]
+	fn allowances_are_bounded_and_cleared_for_their_deposits() {
+		with_externalities(&mut new_test_ext(), || {
+			init_curve();
+			<AllowanceDeposit<Test>>::put(10);
+			assert_ok!(BondedToken::buy(Origin::signed(1), 10, None));
+
+			for spender in 2..2 + u64::from(<Test as Trait>::MAX_ALLOWANCES) {
+				assert_ok!(BondedToken::approve(Origin::signed(1), spender, 1));
+			}
+			assert_noop!(BondedToken::approve(Origin::signed(1), 100, 1), "Too many allowances.");
+			// Topping up an existing allowance takes no new slot or deposit.
+			assert_ok!(BondedToken::approve(Origin::signed(1), 2, 1));
+			assert_eq!(Balances::reserved_balance(&1), 320);
+
+			// Only allowances used up are cleared.
+			assert_ok!(BondedToken::transfer_from(Origin::signed(2), 1, 2, 2));
+			assert_ok!(BondedToken::clear_allowances(Origin::signed(1)));
+			assert_eq!(BondedToken::spenders(1).len(), 31);
+			assert_eq!(Balances::reserved_balance(&1), 310);
+			assert_ok!(BondedToken::approve(Origin::signed(1), 100, 1));
+		});
+	}
+	#[test]
+	fn revoking_all_allowances_empties_the_owner_index() {
+		with_externalities(&mut new_test_ext(), || {
+			init_curve();
+			<AllowanceDeposit<Test>>::put(10);
+			assert_ok!(BondedToken::buy(Origin::signed(1), 10, None));
+			assert_ok!(BondedToken::approve(Origin::signed(1), 2, 5));
+			assert_ok!(BondedToken::approve(Origin::signed(1), 3, 5));
+
+			assert_ok!(BondedToken::revoke_all_allowances(Origin::signed(1)));
+			assert!(BondedToken::spenders(1).is_empty());
+			assert_eq!(Balances::reserved_balance(&1), 0);
+			assert!(!BondedToken::_has_allowance_entry((1, 2)));
+			assert_noop!(BondedToken::transfer_from(Origin::signed(2), 1, 2, 1), "Allowance does not exist.");
+			assert_noop!(BondedToken::revoke_all_allowances(Origin::signed(1)), "No allowances to revoke.");
+		});
+	}
+	#[test]
+	fn queued_updates_apply_only_after_the_delay() {
+		with_externalities(&mut new_test_ext(), || {
+			System::set_block_number(1);
+			assert_ok!(BondedToken::set_update_delay(system::RawOrigin::Root.into(), 5));
+			assert_noop!(
+				BondedToken::set_entry_fee(system::RawOrigin::Root.into(), 100, 0),
+				"Parameter updates must go through the update queue."
+			);
+
+			let update = Box::new(Call::set_entry_fee(100, 0));
+			assert_ok!(BondedToken::queue_update(system::RawOrigin::Root.into(), update.clone()));
+			assert_ok!(BondedToken::queue_update(system::RawOrigin::Root.into(), Box::new(Call::set_entry_fee(200, 0))));
+			assert_noop!(BondedToken::queue_update(Origin::signed(2), update), "Origin must be root or the admin.");
+			assert_noop!(BondedToken::cancel_pending_update(Origin::signed(2), 1), "Origin must be root or the admin.");
+			assert_ok!(BondedToken::cancel_pending_update(system::RawOrigin::Root.into(), 1));
+			assert_noop!(BondedToken::cancel_pending_update(system::RawOrigin::Root.into(), 1), "Update is not pending.");
+
+			BondedToken::on_initialise(5);
+			assert_eq!(BondedToken::entry_fee(), 0);
+			BondedToken::on_initialise(6);
+			assert_eq!(BondedToken::entry_fee(), 100);
+			assert!(BondedToken::pending_update(0).is_none());
+			// The delay itself can only change through the queue.
+			assert!(BondedToken::set_update_delay(system::RawOrigin::Root.into(), 0).is_err());
+		});
+	}
+	#[test]
+	fn vetoes_past_the_threshold_cancel_an_update() {
+		with_externalities(&mut new_test_ext(), || {
+			System::set_block_number(1);
+			init_curve();
+			assert_ok!(BondedToken::buy(Origin::signed(2), 100, None));
+			assert_ok!(BondedToken::buy(Origin::signed(3), 50, None));
+			assert_ok!(BondedToken::set_veto_threshold(system::RawOrigin::Root.into(), 5_000));
+			assert_ok!(BondedToken::set_update_delay(system::RawOrigin::Root.into(), 5));
+
+			assert_ok!(BondedToken::queue_update(system::RawOrigin::Root.into(), Box::new(Call::set_entry_fee(100, 0))));
+			assert_ok!(BondedToken::queue_update(system::RawOrigin::Root.into(), Box::new(Call::set_exit_fee(100))));
+			assert_noop!(BondedToken::veto(Origin::signed(2), 0, 101, Conviction::Locked1x), "Not enough unlocked balance or delegated power.");
+			assert_ok!(BondedToken::veto(Origin::signed(2), 0, 80, Conviction::Locked1x));
+			assert_ok!(BondedToken::veto(Origin::signed(3), 1, 50, Conviction::Locked1x));
+			assert_eq!(BondedToken::veto_tally(0), 80);
+			assert_noop!(BondedToken::transfer(Origin::signed(2), 4, 21), "Tokens are locked by vesting or a veto.");
+
+			// More than half the supply of 150 cancels the fee update; 50 does not stop the other.
+			System::set_block_number(6);
+			BondedToken::on_initialise(6);
+			assert_eq!(BondedToken::entry_fee(), 0);
+			assert_eq!(BondedToken::exit_fee(), 100);
+
+			assert_ok!(BondedToken::withdraw_veto(Origin::signed(2), 0));
+			assert_eq!(BondedToken::veto_locked(2), 0);
+			assert_ok!(BondedToken::transfer(Origin::signed(2), 4, 21));
+		});
+	}
+	#[test]
+	fn quadratic_tallies_count_the_root_of_each_veto() {
+		with_externalities(&mut new_test_ext(), || {
+			System::set_block_number(1);
+			init_curve();
+			assert_ok!(BondedToken::buy(Origin::signed(2), 121, None));
+			assert_ok!(BondedToken::buy(Origin::signed(3), 9_879, None));
+			assert_ok!(BondedToken::set_veto_threshold(system::RawOrigin::Root.into(), 5_000));
+
+			assert_ok!(BondedToken::queue_update(system::RawOrigin::Root.into(), Box::new(Call::set_exit_fee(100))));
+			assert_ok!(BondedToken::set_tally_mode(system::RawOrigin::Root.into(), UpdateCategory::Fees, TallyMode::Quadratic));
+			assert_ok!(BondedToken::queue_update(system::RawOrigin::Root.into(), Box::new(Call::set_entry_fee(100, 0))));
+			assert_eq!(BondedToken::update_tally_mode(0), TallyMode::Linear);
+			assert_eq!(BondedToken::update_tally_mode(1), TallyMode::Quadratic);
+
+			// Topping up a veto adds the growth of the root, not the root of the top-up.
+			assert_ok!(BondedToken::veto(Origin::signed(2), 1, 100, Conviction::Locked1x));
+			assert_eq!(BondedToken::veto_tally(1), 10);
+			assert_ok!(BondedToken::veto(Origin::signed(2), 1, 21, Conviction::Locked1x));
+			assert_eq!(BondedToken::veto_tally(1), 11);
+			assert_ok!(BondedToken::veto(Origin::signed(3), 0, 4_000, Conviction::Locked1x));
+			assert_ok!(BondedToken::veto(Origin::signed(3), 1, 1_600, Conviction::Locked1x));
+			assert_eq!(BondedToken::veto_tally(1), 51);
+
+			// The linear update needs 5,000 votes of the 10,000 supply, the quadratic one 50.
+			System::set_block_number(2);
+			BondedToken::on_initialise(2);
+			assert_eq!(BondedToken::exit_fee(), 100);
+			assert_eq!(BondedToken::entry_fee(), 0);
+		});
+	}
+	#[test]
+	fn conviction_multiplies_votes_and_the_lock() {
+		with_externalities(&mut new_test_ext(), || {
+			System::set_block_number(1);
+			init_curve();
+			assert_ok!(BondedToken::buy(Origin::signed(2), 100, None));
+			assert_ok!(BondedToken::set_veto_threshold(system::RawOrigin::Root.into(), 9_000));
+			assert_ok!(BondedToken::set_vote_lock_era(system::RawOrigin::Root.into(), 10));
+			assert_ok!(BondedToken::queue_update(system::RawOrigin::Root.into(), Box::new(Call::set_exit_fee(100))));
+			assert_ok!(BondedToken::queue_update(system::RawOrigin::Root.into(), Box::new(Call::set_entry_fee(100, 0))));
+
+			assert_ok!(BondedToken::veto(Origin::signed(2), 0, 20, Conviction::Locked4x));
+			assert_eq!(BondedToken::veto_tally(0), 80);
+			assert_noop!(
+				BondedToken::veto(Origin::signed(2), 0, 10, Conviction::Locked1x),
+				"Veto was cast with a different conviction."
+			);
+			// Withdrawing while the update is pending takes the multiplied votes back off.
+			assert_ok!(BondedToken::veto(Origin::signed(2), 1, 30, Conviction::Locked2x));
+			assert_eq!(BondedToken::veto_tally(1), 60);
+			assert_ok!(BondedToken::withdraw_veto(Origin::signed(2), 1));
+			assert_eq!(BondedToken::veto_tally(1), 0);
+
+			// 80 votes fall short of 90% of the supply of 100, so the update applies and the
+			// tokens stay locked for four eras after it was due.
+			System::set_block_number(2);
+			BondedToken::on_initialise(2);
+			assert_eq!(BondedToken::exit_fee(), 100);
+			System::set_block_number(41);
+			assert_noop!(BondedToken::withdraw_veto(Origin::signed(2), 0), "Veto is still locked by its conviction.");
+			System::set_block_number(42);
+			assert_ok!(BondedToken::withdraw_veto(Origin::signed(2), 0));
+			assert_eq!(BondedToken::veto_locked(2), 0);
+		});
+	}
+	#[test]
+	fn delegated_power_counts_once_and_stays_locked_behind_vetoes() {
+		with_externalities(&mut new_test_ext(), || {
+			System::set_block_number(1);
+			init_curve();
+			assert_ok!(BondedToken::buy(Origin::signed(2), 50, None));
+			assert_ok!(BondedToken::buy(Origin::signed(3), 20, None));
+			assert_ok!(BondedToken::set_veto_threshold(system::RawOrigin::Root.into(), 9_000));
+			assert_ok!(BondedToken::queue_update(system::RawOrigin::Root.into(), Box::new(Call::set_exit_fee(100))));
+
+			assert_noop!(BondedToken::delegate(Origin::signed(2), 2, 10), "Cannot delegate to yourself.");
+			assert_ok!(BondedToken::delegate(Origin::signed(2), 3, 50));
+			assert_eq!(BondedToken::delegated_power(3), 50);
+			assert_noop!(BondedToken::transfer(Origin::signed(2), 4, 1), "Tokens are locked by vesting or a veto.");
+
+			// The delegate votes its own tokens first, then the power delegated to it, and
+			// cannot pass that power on.
+			assert_ok!(BondedToken::veto(Origin::signed(3), 0, 70, Conviction::Locked1x));
+			assert_eq!(BondedToken::veto_tally(0), 70);
+			assert_eq!(BondedToken::delegated_power_locked(3), 50);
+			assert_noop!(BondedToken::delegate(Origin::signed(3), 4, 1), "Not enough unlocked balance.");
+			assert_noop!(BondedToken::veto(Origin::signed(2), 0, 1, Conviction::Locked1x), "Not enough unlocked balance or delegated power.");
+			assert_noop!(BondedToken::undelegate(Origin::signed(2)), "Delegated power is locked behind the delegate's vetoes.");
+
+			assert_ok!(BondedToken::withdraw_veto(Origin::signed(3), 0));
+			assert_ok!(BondedToken::undelegate(Origin::signed(2)));
+			assert_eq!(BondedToken::delegated_power(3), 0);
+			assert_eq!(BondedToken::delegation(2), None);
+			assert_ok!(BondedToken::transfer(Origin::signed(2), 4, 50));
+		});
+	}
+	#[test]
+	fn streams_release_tokens_block_by_block() {
+		with_externalities(&mut new_test_ext(), || {
+			System::set_block_number(1);
+			init_curve();
+			assert_ok!(BondedToken::buy(Origin::signed(2), 100, None));
+			assert_noop!(BondedToken::open_stream(Origin::signed(2), 3, 11, 10), "Not enough unlocked balance.");
+			assert_ok!(BondedToken::open_stream(Origin::signed(2), 3, 5, 10));
+			assert_eq!(BondedToken::balance_of(2), 50);
+			assert_eq!(BondedToken::escrowed_tokens(), 50);
+			assert_noop!(BondedToken::withdraw_stream(Origin::signed(3), 0), "Nothing to withdraw yet.");
+
+			System::set_block_number(4);
+			assert_noop!(BondedToken::withdraw_stream(Origin::signed(2), 0), "Not the recipient of this stream.");
+			assert_ok!(BondedToken::withdraw_stream(Origin::signed(3), 0));
+			assert_eq!(BondedToken::balance_of(3), 15);
+
+			// Cancelling pays out what has been released and refunds the rest.
+			System::set_block_number(6);
+			assert_noop!(BondedToken::cancel_stream(Origin::signed(4), 0), "Not a party to this stream.");
+			assert_ok!(BondedToken::cancel_stream(Origin::signed(2), 0));
+			assert_eq!(BondedToken::balance_of(3), 25);
+			assert_eq!(BondedToken::balance_of(2), 75);
+			assert_eq!(BondedToken::escrowed_tokens(), 0);
+			assert!(BondedToken::stream(0).is_none());
+			assert_ok!(BondedToken::audit_supply());
+		});
+	}
+	#[test]
+	fn escrows_settle_by_the_arbiter_or_after_the_deadline() {
+		with_externalities(&mut new_test_ext(), || {
+			System::set_block_number(1);
+			init_curve();
+			assert_ok!(BondedToken::buy(Origin::signed(2), 100, None));
+			assert_noop!(BondedToken::escrow(Origin::signed(2), 3, 4, 10, 1), "Escrow deadline has passed.");
+			assert_ok!(BondedToken::escrow(Origin::signed(2), 3, 4, 30, 10));
+			assert_ok!(BondedToken::escrow(Origin::signed(2), 3, 4, 20, 10));
+			assert_eq!(BondedToken::balance_of(2), 50);
+
+			// The recipient cannot release to itself, but the arbiter can.
+			assert_noop!(BondedToken::release_escrow(Origin::signed(3), 0), "Not allowed to release this escrow.");
+			assert_ok!(BondedToken::release_escrow(Origin::signed(4), 0));
+			assert_eq!(BondedToken::balance_of(3), 30);
+
+			// The funder can only take an escrow back after its deadline.
+			assert_noop!(BondedToken::refund_escrow(Origin::signed(2), 1), "Not allowed to refund this escrow.");
+			System::set_block_number(11);
+			assert_ok!(BondedToken::refund_escrow(Origin::signed(2), 1));
+			assert_eq!(BondedToken::balance_of(2), 70);
+			assert_noop!(BondedToken::release_escrow(Origin::signed(4), 1), "Escrow does not exist.");
+			assert_eq!(BondedToken::escrowed_tokens(), 0);
+		});
+	}
+	#[test]
+	fn hashed_timelocks_pay_out_on_the_preimage_or_refund_after_the_timeout() {
+		with_externalities(&mut new_test_ext(), || {
+			System::set_block_number(1);
+			init_curve();
+			assert_ok!(BondedToken::buy(Origin::signed(2), 100, None));
+			let secret = b"secret".to_vec();
+			let hash = BlakeTwo256::hash(&secret);
+			let other = BlakeTwo256::hash(b"other");
+			assert_ok!(BondedToken::htlc_lock(Origin::signed(2), hash, 3, 40, 10));
+			assert_noop!(BondedToken::htlc_lock(Origin::signed(2), hash, 3, 10, 10), "Hash is already locked.");
+			assert_ok!(BondedToken::htlc_lock(Origin::signed(2), other, 3, 10, 10));
+			assert_noop!(BondedToken::htlc_refund(Origin::signed(2), hash), "Hashed timelock has not timed out.");
+
+			// Anyone with the preimage can claim it for the recipient.
+			assert_noop!(BondedToken::htlc_claim(Origin::signed(4), b"guess".to_vec()), "No hashed timelock for this preimage.");
+			assert_ok!(BondedToken::htlc_claim(Origin::signed(4), secret));
+			assert_eq!(BondedToken::balance_of(3), 40);
+
+			System::set_block_number(10);
+			assert_noop!(BondedToken::htlc_claim(Origin::signed(3), b"other".to_vec()), "Hashed timelock has timed out.");
+			assert_ok!(BondedToken::htlc_refund(Origin::signed(3), other));
+			assert_eq!(BondedToken::balance_of(2), 60);
+			assert_eq!(BondedToken::escrowed_tokens(), 0);
+		});
+	}
+	#[test]
+	fn an_unpaid_flash_mint_is_backed_from_the_collateral() {
+		with_externalities(&mut new_test_ext(), || {
+			init_curve();
+			assert_ok!(BondedToken::buy(Origin::signed(2), 100, None));
+			assert_ok!(BondedToken::set_flash_mint_config(system::RawOrigin::Root.into(), 50, 100));
+			assert_noop!(BondedToken::flash_mint(Origin::signed(3), 51, b"repay".to_vec()), "Flash mint is above the limit.");
+
+			// A failed strategy gets all of its collateral back.
+			let before = Balances::free_balance(&3);
+			assert_eq!(BondedToken::flash_mint(Origin::signed(3), 50, b"other".to_vec()), Err("Unknown strategy."));
+			assert_eq!(Balances::free_balance(&3), before);
+
+			// A repaid flash mint costs only the fee.
+			assert_ok!(BondedToken::flash_mint(Origin::signed(3), 50, b"repay".to_vec()));
+			let fee = before - Balances::free_balance(&3);
+			assert!(fee > 0);
+			assert_eq!(BondedToken::funding_pool(), fee);
+			assert_eq!(BondedToken::total_supply(), 100);
+
+			// Unpaid tokens join the supply and the collateral keeps the curve solvent.
+			let before = Balances::free_balance(&3);
+			let reserve = BondedToken::reserve();
+			assert_eq!(BondedToken::flash_mint(Origin::signed(3), 50, b"default".to_vec()), Err("Flash mint was not repaid."));
+			assert_eq!(BondedToken::flash_minted(), 0);
+			assert_eq!(BondedToken::balance_of(9), 50);
+			assert_eq!(BondedToken::total_supply(), 150);
+			assert_eq!(BondedToken::funding_pool(), 2 * fee);
+			let kept = BondedToken::reserve() - reserve;
+			assert!(kept > 0);
+			assert_eq!(before - Balances::free_balance(&3), fee + kept);
+			assert_ok!(BondedToken::audit_solvency());
+		});
+	}
+	#[test]
+	fn transfers_above_the_threshold_wait_for_the_cosigner() {
+		with_externalities(&mut new_test_ext(), || {
+			System::set_block_number(1);
+			init_curve();
+			assert_ok!(BondedToken::buy(Origin::signed(2), 100, None));
+			assert_noop!(BondedToken::set_cosigner(Origin::signed(2), 2, 20), "Cannot cosign your own transfers.");
+			assert_ok!(BondedToken::set_cosigner(Origin::signed(2), 4, 20));
+
+			assert_ok!(BondedToken::transfer(Origin::signed(2), 3, 20));
+			assert_eq!(BondedToken::balance_of(3), 20);
+			assert_ok!(BondedToken::transfer(Origin::signed(2), 3, 30));
+			assert_ok!(BondedToken::escrow(Origin::signed(2), 3, 4, 30, 5));
+			assert_eq!(BondedToken::balance_of(2), 20);
+			assert_eq!(BondedToken::balance_of(3), 20);
+
+			assert_noop!(BondedToken::approve_transfer(Origin::signed(3), 0), "Only the cosigner can approve this transfer.");
+			assert_ok!(BondedToken::approve_transfer(Origin::signed(4), 0));
+			assert_eq!(BondedToken::balance_of(3), 50);
+
+			// An escrow approved after its deadline is returned instead of opened.
+			System::set_block_number(6);
+			assert_ok!(BondedToken::approve_transfer(Origin::signed(4), 1));
+			assert!(BondedToken::escrows(0).is_none());
+			assert_eq!(BondedToken::balance_of(2), 50);
+			assert_eq!(BondedToken::escrowed_tokens(), 0);
+
+			// The sender can take back a held transfer but not drop the policy.
+			assert_ok!(BondedToken::transfer(Origin::signed(2), 3, 25));
+			assert_ok!(BondedToken::cancel_transfer(Origin::signed(2), 2));
+			assert_eq!(BondedToken::balance_of(2), 50);
+			assert_noop!(BondedToken::remove_cosigner(Origin::signed(2), 2), "Only the cosigner can remove its policy.");
+			assert_ok!(BondedToken::remove_cosigner(Origin::signed(4), 2));
+			assert_ok!(BondedToken::transfer(Origin::signed(2), 3, 25));
+			assert_eq!(BondedToken::balance_of(3), 75);
+		});
+	}
+	#[test]
+	fn spend_limits_cap_every_outflow_in_a_window() {
+		with_externalities(&mut new_test_ext(), || {
+			System::set_block_number(1);
+			init_curve();
+			assert_ok!(BondedToken::buy(Origin::signed(2), 200, None));
+			assert_ok!(BondedToken::set_redemption_enabled(system::RawOrigin::Root.into(), true));
+			assert_ok!(BondedToken::set_spend_limit(Origin::signed(2), 50, 10));
+			assert_noop!(BondedToken::set_spend_limit(Origin::signed(2), 60, 10), "Spend limit can only be tightened.");
+
+			assert_ok!(BondedToken::transfer(Origin::signed(2), 3, 20));
+			assert_ok!(BondedToken::open_stream(Origin::signed(2), 3, 1, 10));
+			assert_ok!(BondedToken::sell(Origin::signed(2), 10, None));
+			assert_ok!(BondedToken::redeem_underlying(Origin::signed(2), 10));
+			assert_noop!(BondedToken::transfer(Origin::signed(2), 3, 1), "Spend limit reached for this window.");
+			assert_noop!(BondedToken::sell(Origin::signed(2), 1, None), "Spend limit reached for this window.");
+			assert_noop!(BondedToken::redeem_underlying(Origin::signed(2), 1), "Spend limit reached for this window.");
+
+			// A new window starts afresh, and lifting the limit takes a full window.
+			System::set_block_number(11);
+			assert_ok!(BondedToken::lift_spend_limit(Origin::signed(2)));
+			assert_ok!(BondedToken::transfer(Origin::signed(2), 3, 50));
+			assert_noop!(BondedToken::transfer(Origin::signed(2), 3, 1), "Spend limit reached for this window.");
+			System::set_block_number(21);
+			assert_ok!(BondedToken::transfer(Origin::signed(2), 3, 1));
+
+			// A limit imposed by governance cannot be lifted by the holder.
+			assert_ok!(BondedToken::impose_spend_limit(system::RawOrigin::Root.into(), 3, Some((5, 10))));
+			assert_noop!(BondedToken::lift_spend_limit(Origin::signed(3)), "Spend limit was imposed by the admin.");
+			assert_noop!(BondedToken::transfer(Origin::signed(3), 2, 6), "Spend limit reached for this window.");
+		});
+	}
+	#[test]
+	fn reversible_transfers_complete_after_the_dispute_window() {
+		with_externalities(&mut new_test_ext(), || {
+			System::set_block_number(1);
+			init_curve();
+			assert_ok!(BondedToken::buy(Origin::signed(2), 100, None));
+			assert_noop!(BondedToken::set_reversible_transfers(Origin::signed(2), Some((2, 5))), "Cannot guard your own transfers.");
+			assert_ok!(BondedToken::set_reversible_transfers(Origin::signed(2), Some((4, 5))));
+
+			assert_ok!(BondedToken::transfer(Origin::signed(2), 3, 30));
+			assert_ok!(BondedToken::transfer(Origin::signed(2), 3, 20));
+			assert_ok!(BondedToken::htlc_lock(Origin::signed(2), BlakeTwo256::hash(b"secret"), 3, 10, 4));
+			assert_eq!(BondedToken::balance_of(2), 40);
+			assert_eq!(BondedToken::balance_of(3), 0);
+
+			assert_noop!(BondedToken::reverse_transfer(Origin::signed(3), 1), "Not allowed to reverse this transfer.");
+			assert_ok!(BondedToken::reverse_transfer(Origin::signed(4), 1));
+			assert_eq!(BondedToken::balance_of(2), 60);
+
+			// The hashed timelock timed out while held, so it goes back instead of opening.
+			System::set_block_number(6);
+			BondedToken::on_initialise(6);
+			assert_eq!(BondedToken::balance_of(3), 30);
+			assert_eq!(BondedToken::balance_of(2), 70);
+			assert!(BondedToken::htlc(BlakeTwo256::hash(b"secret")).is_none());
+			assert_eq!(BondedToken::escrowed_tokens(), 0);
+			assert_noop!(BondedToken::reverse_transfer(Origin::signed(2), 0), "Reversible transfer does not exist.");
+		});
+	}
+	#[test]
+	fn an_auction_clears_at_one_price_and_refunds_the_excess_pro_rata() {
+		with_externalities(&mut new_test_ext(), || {
+			System::set_block_number(1);
+			init_curve();
+			assert_ok!(BondedToken::start_auction(system::RawOrigin::Root.into(), 100, 100, 60, 9));
+			assert_ok!(BondedToken::bid(Origin::signed(2), 2_000));
+			assert_ok!(BondedToken::bid(Origin::signed(3), 3_000));
+			assert_noop!(BondedToken::claim_auction(Origin::signed(2)), "The auction has not closed.");
+
+			// The tranche never sold out, so it clears at the floor and the 5,000 committed
+			// buy 83 tokens, leaving 20 to refund.
+			BondedToken::on_initialise(10);
+			let auction = BondedToken::auction().unwrap();
+			assert_eq!(auction.clearing_price, Some(60));
+			assert_eq!(BondedToken::total_supply(), 83);
+			assert_noop!(BondedToken::bid(Origin::signed(4), 100), "The auction has closed.");
+
+			assert_ok!(BondedToken::claim_auction(Origin::signed(2)));
+			assert_ok!(BondedToken::claim_auction(Origin::signed(3)));
+			assert_eq!(BondedToken::balance_of(2), 33);
+			assert_eq!(BondedToken::balance_of(3), 49);
+			assert_eq!(Balances::free_balance(&2), 1_000_000_000 - 2_000 + 8);
+			assert_eq!(Balances::free_balance(&3), 1_000_000_000 - 3_000 + 12);
+			assert_noop!(BondedToken::claim_auction(Origin::signed(2)), "No bid to claim.");
+			// The token lost to rounding stays unclaimed within the supply.
+			assert_eq!(BondedToken::auction_unclaimed(), 1);
+			assert_ok!(BondedToken::audit_supply());
+		});
+	}
+	#[test]
+	fn a_failed_solvency_check_pauses_trading_until_root_resumes_it() {
+		with_externalities(&mut new_test_ext(), || {
+			init_curve();
+			assert_ok!(BondedToken::buy(Origin::signed(2), 10, None));
+			BondedToken::on_finalise(1);
+			assert_eq!(BondedToken::paused_at(), None);
+
+			// Losing the reserve leaves the supply unbacked.
+			let reserve = BondedToken::reserve();
+			<Reserve<Test>>::put(0);
+			BondedToken::on_finalise(2);
+			assert_eq!(BondedToken::paused_at(), Some(2));
+			assert_noop!(BondedToken::buy(Origin::signed(2), 1, None), "Trading is paused.");
+			assert_noop!(BondedToken::sell(Origin::signed(2), 1, None), "Trading is paused.");
+
+			// Once governance is renounced, the former admin can no longer resume.
+			assert_ok!(BondedToken::transfer_admin(system::RawOrigin::Root.into(), 1));
+			assert_ok!(BondedToken::renounce_admin(Origin::signed(1)));
+			<Reserve<Test>>::put(reserve);
+			assert!(BondedToken::resume_trading(Origin::signed(1)).is_err());
+			assert_ok!(BondedToken::resume_trading(system::RawOrigin::Root.into()));
+			assert_noop!(BondedToken::resume_trading(system::RawOrigin::Root.into()), "Trading is not paused.");
+
+			BondedToken::on_finalise(3);
+			assert_eq!(BondedToken::paused_at(), None);
+			assert_ok!(BondedToken::buy(Origin::signed(2), 1, None));
+		});
+	}
+}
@@ -18,7 +18,7 @@ use runtime_primitives::{
 };
 use client::{
 	block_builder::api::{CheckInherentsResult, InherentData, self as block_builder_api},
-	runtime_api, impl_runtime_apis
+	runtime_api, impl_runtime_apis, decl_runtime_apis
 };
 use version::RuntimeVersion;
 #[cfg(feature = "std")]
@@ -47,7 +47,23 @@ pub type BlockNumber = u64;
 pub type Nonce = u64;
 
 /// Bonded Token module
-mod bonded_token;
+pub mod bonded_token;
+
+decl_runtime_apis! {
+	/// Trade quotes against the bonded token curve and reads of its state.
+	pub trait BondedTokenApi {
+		/// Cost breakdown of buying `tokens`, or `None` if the buy is impossible.
+		fn estimate_buy(tokens: u128) -> Option<bonded_token::TradeQuote>;
+		/// Payout breakdown of selling `tokens`, or `None` if the sell is impossible.
+		fn estimate_sell(tokens: u128) -> Option<bonded_token::TradeQuote>;
+		/// Supply, reserve, price, curve and fee settings of the token in one struct.
+		fn token_state() -> bonded_token::TokenState;
+		/// State trie keys the balance of `who` may be stored under.
+		fn balance_keys(who: AccountId) -> bonded_token::StorageKeys;
+		/// State trie keys the allowance `owner` gave `spender` may be stored under.
+		fn allowance_keys(owner: AccountId, spender: AccountId) -> bonded_token::StorageKeys;
+	}
+}
 
 /// Opaque types. These are used by the CLI to instantiate machinery that don't need to know
 /// the specifics of the runtime. They can then be made to be agnostic over specific formats
@@ -181,6 +197,31 @@ impl bonded_token::Trait for Runtime {
 	type Event = Event;
 	/// The type for recording an account's token balance.
 	type TokenBalance = u128;
+	/// No module reacts to `transfer_and_call` yet.
+	type OnTokenReceived = ();
+	/// Nothing is attached to trades.
+	type OnBuy = ();
+	type OnSell = ();
+	/// The reserve is never deployed.
+	type ReserveManager = ();
+	type Counterpart = ();
+	type PriceOracle = ();
+	/// No oracle module consumes the token's price yet.
+	type PriceFeed = ();
+	/// Flash mints are disabled until a strategy module is added.
+	type FlashBorrower = ();
+	/// Leftover tribute goes to the token's own funding pool.
+	type FeeHandler = BondedToken;
+	/// Balances and token amounts are both `u128`, so no conversion can fail.
+	type BalanceConversion = bonded_token::IdentityConversion;
+
+	const MIN_TRADE: u128 = 1;
+	/// Tributes are capped at 10%.
+	const MAX_ENTRY_FEE: u32 = 1_000;
+	const MAX_EXIT_FEE: u32 = 1_000;
+	const MAX_SUPPLY: u128 = 1_000_000_000_000_000_000;
+	const MAX_ALLOWANCES: u32 = 32;
+	const TRACK_COST_BASIS: bool = true;
 }
 
 construct_runtime!(
@@ -197,7 +238,7 @@ construct_runtime!(
 		Balances: balances,
 		Sudo: sudo,
 		Fees: fees::{Module, Storage, Config<T>, Event<T>},
-		BondedToken: bonded_token::{Module, Call, Storage, Event<T>},
+		BondedToken: bonded_token::{Module, Call, Storage, Config<T>, Event<T>},
 	}
 );
 
@@ -277,4 +318,26 @@ impl_runtime_apis! {
 			Aura::slot_duration()
 		}
 	}
+
+	impl self::BondedTokenApi<Block> for Runtime {
+		fn estimate_buy(tokens: u128) -> Option<bonded_token::TradeQuote> {
+			BondedToken::estimate_buy(tokens)
+		}
+
+		fn estimate_sell(tokens: u128) -> Option<bonded_token::TradeQuote> {
+			BondedToken::estimate_sell(tokens)
+		}
+
+		fn token_state() -> bonded_token::TokenState {
+			BondedToken::token_state()
+		}
+
+		fn balance_keys(who: AccountId) -> bonded_token::StorageKeys {
+			BondedToken::balance_keys(&who)
+		}
+
+		fn allowance_keys(owner: AccountId, spender: AccountId) -> bonded_token::StorageKeys {
+			BondedToken::allowance_keys((owner, spender))
+		}
+	}
 }
@@ -1,7 +1,7 @@
 use primitives::{Ed25519AuthorityId, ed25519};
 use node_template_runtime::{
 	AccountId, GenesisConfig, ConsensusConfig, TimestampConfig, BalancesConfig,
-	SudoConfig, IndicesConfig, FeesConfig,
+	SudoConfig, IndicesConfig, FeesConfig, BondedTokenConfig,
 };
 use substrate_service;
 
@@ -102,6 +102,10 @@ fn testnet_genesis(initial_authorities: Vec<Ed25519AuthorityId>, endowed_account
 		fees: Some(FeesConfig {
 			transaction_base_fee: 1,
 			transaction_byte_fee: 0,
-		})
+		}),
+		bonded_token: Some(BondedTokenConfig {
+			presale: vec![],
+			update_delay: 7200,		// 10 hours at 5 second blocks.
+		}),
 	}
 }